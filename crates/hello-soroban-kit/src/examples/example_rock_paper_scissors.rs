@@ -24,6 +24,8 @@
 
 // Additionally, the soroban-kit `state-machine` macro is used to model the game's state transitions
 // with concurrency and extended states to allow players to play in any order.
+// Phase changes are published as events (`events = true`) so off-chain UIs can track the game.
+// The last phase changes of each player are also recorded (`history = 4`) to settle disputes.
 
 use soroban_sdk::{bytes, symbol_short, Bytes, BytesN, Env, Symbol,};
 
@@ -50,11 +52,11 @@ impl RockPaperScissors {
                 match phase {
                     Phase::Committing(Player::Alice) => {
                         // Alice played, transition to revealing phase.
-                        state_machine.set_state(&env, &Phase::Revealing(Player::Alice));
+                        state_machine.transition(&env, &Phase::Revealing(Player::Alice));
                     }
                     Phase::Revealing(Player::Alice) => {
                         // Alice revealed, transition to completed phase.
-                        state_machine.set_state(&env, &Phase::Completed(Player::Alice));
+                        state_machine.transition(&env, &Phase::Completed(Player::Alice));
                         // If Bob also played, set game to End phase.
                         end_game_if_completed(&env, Player::Alice, Player::Bob);
                     }
@@ -65,11 +67,11 @@ impl RockPaperScissors {
                 match phase {
                     Phase::Committing(Player::Bob) => {
                         // Bob played, transition to revealing phase.
-                        state_machine.set_state(&env, &Phase::Revealing(Player::Bob));
+                        state_machine.transition(&env, &Phase::Revealing(Player::Bob));
                     }
                     Phase::Revealing(Player::Bob) => {
                         // Bob revealed, transition to completed phase.
-                        state_machine.set_state(&env, &Phase::Completed(Player::Bob));
+                        state_machine.transition(&env, &Phase::Completed(Player::Bob));
                         // If Alice also played, set game to End phase.
                         end_game_if_completed(&env, Player::Bob, Player::Alice);
                     }
@@ -82,7 +84,8 @@ impl RockPaperScissors {
         // Game completion helper.
         fn end_game_if_completed(env: &Env, for_player: Player, check_player: Player) {
             let state_machine =
-                StateMachine::<Domain, Phase>::new(&Domain::Game, StorageType::Instance);
+                StateMachine::<Domain, Phase>::new(&Domain::Game, StorageType::Instance)
+                    .with_events(true);
             let next_phase = match state_machine.get_state(env).unwrap_or(Phase::Start) {
                 Phase::Completed(player) if player == check_player => Phase::End,
                 _ => Phase::Completed(for_player),
@@ -101,12 +104,12 @@ impl RockPaperScissors {
 
     // Commit phase.
     #[commit]
-    #[state_machine(state = "Phase:Committing:player", region = "Domain:Players:player")]
+    #[state_machine(state = "Phase:Committing:player", region = "Domain:Players:player", events = true, history = 4)]
     fn play(&self, env: &Env, player: &Player, hash: &BytesN<32>) {}
 
     // Reveal phase.
     #[reveal]
-    #[state_machine(state = "Phase:Revealing:player", region = "Domain:Players:player")]
+    #[state_machine(state = "Phase:Revealing:player", region = "Domain:Players:player", events = true, history = 4)]
     fn reveal(&self, env: &Env, player: &Player, data: &Bytes) {}
 
     // Solve phase.
@@ -118,7 +121,10 @@ impl RockPaperScissors {
     fn reset_player(&self, env: &Env, player: Player) {
         let domain = Domain::Players(player.clone());
         let phase = Phase::Committing(player.clone());
-        let state_machine = StateMachine::<Domain, Phase>::new(&domain, StorageType::Instance);
+        // Same configuration as play and reveal, so that resets are recorded.
+        let state_machine = StateMachine::<Domain, Phase>::new(&domain, StorageType::Instance)
+            .with_events(true)
+            .with_history(4);
         state_machine.set_state(&env, &phase);
     }
 }
@@ -17,7 +17,7 @@ mod types; // Contract types.
 
 use examples::{example_circuit_breaker, example_rock_paper_scissors, example_storage};
 
-use soroban_kit::{oracle, oracle_subscriber, storage};
+use soroban_kit::{oracle, oracle_subscriber, soroban_tools, storage};
 use soroban_sdk::{contract, contractimpl, Address, Bytes, Env, Symbol, Vec};
 
 use types::{Message, MessageKey, Whitelist, WhitelistKey};
@@ -19,7 +19,7 @@ use soroban_sdk::{
     vec, Address, Bytes, BytesN, Env, Symbol, Vec,
 };
 
-use std::{panic::catch_unwind, println};
+use std::{panic::catch_unwind, println, string::ToString};
 
 use soroban_kit::{
     commit, fsm,
@@ -288,7 +288,7 @@ impl Circuit {
 
 // Oracle service module.
 pub mod oracle_service {
-    use soroban_kit::{oracle::Envelope, oracle::Events, oracle_broker};
+    use soroban_kit::{oracle::Envelope, oracle::Events, oracle_broker, soroban_tools};
     use soroban_sdk::{
         contract, contractimpl, contracttype, symbol_short, token, Address, Bytes, Env, Symbol, Vec,
     };
@@ -600,8 +600,16 @@ fn test_soroban_kit_hello_state_machine() {
 #[test]
 fn test_soroban_kit_hello_storage() {
     let env = Env::default();
-    TestContractClient::new(&env, &env.register_contract(None, TestContract))
-        .hello_storage(&symbol_short!("Fred"));
+    let client = TestContractClient::new(&env, &env.register_contract(None, TestContract));
+    let before = storage::snapshot(&env);
+    client.hello_storage(&symbol_short!("Fred"));
+
+    // The newcomer is stored in the instance storage.
+    let diff = storage::diff(&before, &storage::snapshot(&env));
+    assert_eq!(diff.len(), 1);
+    assert!(diff.to_string().starts_with("+ "));
+    assert!(matches!(&diff.0[0], storage::Change::Added(entry)
+        if entry.tier == storage::StorageTier::Instance));
 }
 
 #[test]
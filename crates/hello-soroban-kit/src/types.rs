@@ -10,8 +10,8 @@
     Author: Fred Kyung-jin Rezeau <fred@litemint.com>
 */
 
-use soroban_kit::{key_constraint, soroban_tools, storage};
-use soroban_sdk::{contracttype, Address, Bytes, Env, Symbol};
+use soroban_kit::{key_constraint, soroban_tools, state_machine_def, storage};
+use soroban_sdk::{contracttype, Address, Bytes, Symbol};
 
 // Optional but recommended.
 // Use `key_constraint` to apply a constraint to the Key
@@ -55,7 +55,17 @@ pub enum Player {
     Bob,
 }
 
+// Use `state_machine_def` to declare the legal transitions between
+// phases (see StateMachine::transition). Edges are declared per variant,
+// not per payload: `Committing -> Revealing` also allows moving from
+// `Committing(Player::Alice)` to `Revealing(Player::Bob)`.
 #[contracttype]
+#[state_machine_def(
+    Start -> Committing,
+    Committing -> Revealing,
+    Revealing -> Completed,
+    Completed -> End,
+)]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Phase {
     Start,
@@ -15,7 +15,7 @@
 
 #![no_std]
 
-use soroban_kit::{oracle, oracle_broker};
+use soroban_kit::{oracle, oracle_broker, soroban_tools};
 use soroban_sdk::{
     contract, contractimpl, contracttype, token, Address, Bytes, Env, TryIntoVal, Vec,
 };
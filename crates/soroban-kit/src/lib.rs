@@ -24,4 +24,8 @@ pub use soroban_tools::*;
 // Explicit since fsm::TransitionHandler trait is feature gated.
 #[cfg(feature = "state-machine")]
 // Bring this trait in scope for #[derive(TransitionHandler)]
-pub use soroban_tools::fsm::TransitionHandler;
\ No newline at end of file
+pub use soroban_tools::fsm::TransitionHandler;
+
+#[cfg(feature = "state-machine")]
+// Bring this trait in scope for #[derive(FallibleTransitionHandler)]
+pub use soroban_tools::fsm::FallibleTransitionHandler;
\ No newline at end of file
@@ -48,7 +48,9 @@ pub fn commit(attr: TokenStream, input: TokenStream) -> TokenStream {
             _ => quote! { env.storage().instance() },
         };
         quote! {
-            assert!(!#storage.has::<BytesN<32>>(&#hash_expr));
+            if #storage.has::<BytesN<32>>(&#hash_expr) {
+                soroban_sdk::panic_with_error!(env, soroban_tools::error::KitError::AlreadyCommitted);
+            }
             #storage.set::<BytesN<32>, i32>(&#hash_expr, &0i32);
         }
     };
@@ -116,7 +118,9 @@ pub fn reveal(attr: TokenStream, input: TokenStream) -> TokenStream {
                 _ => unimplemented!()
             };
 
-            assert!(#storage.has::<BytesN<32>>(&computed_hash));
+            if !#storage.has::<BytesN<32>>(&computed_hash) {
+                soroban_sdk::panic_with_error!(env, soroban_tools::error::KitError::CommitmentNotFound);
+            }
             if #remove {
                 #storage.remove::<BytesN<32>>(&computed_hash);
             }
@@ -12,8 +12,14 @@
 
 extern crate proc_macro;
 use proc_macro::TokenStream;
+use proc_macro2::TokenTree;
 use quote::{format_ident, quote};
-use syn::{parse_macro_input, AttributeArgs, DeriveInput, Ident, ItemFn, Lit, Meta, NestedMeta};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    AttributeArgs, DeriveInput, Ident, ItemEnum, ItemFn, Lit, Meta, NestedMeta, Token,
+};
 
 #[allow(unused_imports)]
 use soroban_tools::fsm::StorageType;
@@ -41,11 +47,41 @@ pub fn transition_handler_derive(input: TokenStream) -> TokenStream {
     expanded.into()
 }
 
+pub fn fallible_transition_handler_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let ty = input.ident;
+    let expanded = quote! {
+        impl<K, V, E> soroban_tools::fsm::FallibleTransitionHandler<K, V, E> for #ty
+        where
+            K: Clone
+                + soroban_sdk::IntoVal<soroban_sdk::Env, soroban_sdk::Val>
+                + soroban_sdk::TryFromVal<soroban_sdk::Env, soroban_sdk::Val>,
+            V: Clone
+                + soroban_sdk::IntoVal<soroban_sdk::Env, soroban_sdk::Val>
+                + soroban_sdk::TryFromVal<soroban_sdk::Env, soroban_sdk::Val>,
+        {
+            fn on_guard(&self, _env: &soroban_sdk::Env, _state_machine: &soroban_tools::fsm::StateMachine<K, V>) -> Result<(), E> {
+                Ok(())
+            }
+
+            fn on_effect(&self, _env: &soroban_sdk::Env, _state_machine: &soroban_tools::fsm::StateMachine<K, V>) -> Result<(), E> {
+                Ok(())
+            }
+        }
+    };
+    expanded.into()
+}
+
 pub fn state_machine(attr: TokenStream, input: TokenStream) -> TokenStream {
+    let (attr, error_type) = match split_error_type(attr.into()) {
+        Ok(result) => result,
+        Err(error) => return error.to_compile_error().into(),
+    };
     let args = parse_macro_input!(attr as AttributeArgs);
     let mut input_fn = parse_macro_input!(input as ItemFn);
 
-    let (state_path, region_path, storage_type) = parse_attributes(&args);
+    let (state_path, region_path, storage_type, events, history, timeout_path) =
+        parse_attributes(&args);
 
     // Convert params and call soroban_tools::impl_state_machine! macro.
     let state_enum = state_path[0]
@@ -76,6 +112,26 @@ pub fn state_machine(attr: TokenStream, input: TokenStream) -> TokenStream {
 
     let storage_type_ident = get_storage_type(&storage_type);
 
+    // Function name recorded in the transition history, optional event publishing,
+    // history capacity, timeout state and error type (fallible mode).
+    let function = input_fn.sig.ident.to_string();
+    let events = events.then(|| quote! { ; events = true });
+    let history = (history > 0).then(|| quote! { ; history = #history });
+    let timeout = match timeout_path {
+        [Some(timeout_enum), Some(timeout_variant), tuple_value] => {
+            let timeout_enum = format_ident!("{}", timeout_enum);
+            let timeout_variant = format_ident!("{}", timeout_variant);
+            match tuple_value.map(|p| syn::parse_str::<syn::Expr>(&p).unwrap()) {
+                Some(value) => quote! { ; timeout = #timeout_enum::#timeout_variant(#value.clone()) },
+                None => quote! { ; timeout = #timeout_enum::#timeout_variant },
+            }
+        }
+        [None, None, None] => quote! {},
+        _ => panic!("Expected timeout = \"EnumName:VariantName[:TupleVariableName]\""),
+    };
+    let error = error_type.as_ref().map(|ty| quote! { ; error = #ty });
+    let options = quote! { ; function = #function #events #history #timeout #error };
+
     let state_machine_body = impl_state_machine(
         &state_path,
         &region_path,
@@ -86,6 +142,7 @@ pub fn state_machine(attr: TokenStream, input: TokenStream) -> TokenStream {
         &region_variant,
         &region_tuple_value_expr,
         &storage_type_ident,
+        &options,
     );
 
     // Prepend state machine code to function body.
@@ -101,6 +158,7 @@ pub fn state_machine(attr: TokenStream, input: TokenStream) -> TokenStream {
     TokenStream::from(quote!(#input_fn))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn impl_state_machine(
     state_path: &[Option<String>; 3],
     region_path: &[Option<String>; 3],
@@ -111,6 +169,7 @@ pub fn impl_state_machine(
     region_variant: &Ident,
     region_tuple_value_expr: &syn::Expr,
     storage_type_ident: &Ident,
+    options: &proc_macro2::TokenStream,
 ) -> proc_macro2::TokenStream {
     {
         match (state_path[2].clone(), region_path[2].clone()) {
@@ -123,6 +182,7 @@ pub fn impl_state_machine(
                             soroban_tools::fsm::StorageType::#storage_type_ident,
                             #state_enum, #state_variant, (),
                             #region_enum, #region_variant, ()
+                            #options
                         );
                     }
                 }
@@ -133,6 +193,7 @@ pub fn impl_state_machine(
                             env,
                             soroban_tools::fsm::StorageType::#storage_type_ident,
                             #state_enum, #state_variant
+                            #options
                         );
                     }
                 }
@@ -145,6 +206,7 @@ pub fn impl_state_machine(
                         soroban_tools::fsm::StorageType::#storage_type_ident,
                         #state_enum, #state_variant, (),
                         #region_enum, #region_variant, ( #region_tuple_value_expr.clone() )
+                        #options
                     );
                 }
             }
@@ -157,6 +219,7 @@ pub fn impl_state_machine(
                             soroban_tools::fsm::StorageType::#storage_type_ident,
                             #state_enum, #state_variant, ( #state_tuple_value_expr.clone() ),
                             #region_enum, #region_variant, ()
+                            #options
                         );
                     }
                 }
@@ -167,6 +230,7 @@ pub fn impl_state_machine(
                             env,
                             soroban_tools::fsm::StorageType::#storage_type_ident,
                             #state_enum, #state_variant, ( #state_tuple_value_expr.clone() )
+                            #options
                         );
                     }
                 }
@@ -179,6 +243,7 @@ pub fn impl_state_machine(
                         soroban_tools::fsm::StorageType::#storage_type_ident,
                         #state_enum, #state_variant, ( #state_tuple_value_expr.clone() ),
                         #region_enum, #region_variant, ( #region_tuple_value_expr.clone() )
+                        #options
                     );
                 }
             }
@@ -186,30 +251,79 @@ pub fn impl_state_machine(
     }
 }
 
-pub fn parse_attributes(
-    args: &AttributeArgs,
-) -> ([Option<String>; 3], [Option<String>; 3], Option<String>) {
+// Extract the `error = Type` argument (fallible mode), which is not a
+// literal and therefore not parsed as AttributeArgs.
+fn split_error_type(
+    attr: proc_macro2::TokenStream,
+) -> syn::Result<(TokenStream, Option<syn::Type>)> {
+    let mut args: Vec<Vec<TokenTree>> = vec![vec![]];
+    for token in attr {
+        match &token {
+            TokenTree::Punct(punct) if punct.as_char() == ',' => args.push(vec![]),
+            _ => args.last_mut().unwrap().push(token),
+        }
+    }
+
+    let mut error_type = None;
+    let mut remaining = proc_macro2::TokenStream::new();
+    for arg in args.into_iter().filter(|arg| !arg.is_empty()) {
+        match (&arg[0], arg.get(1)) {
+            (TokenTree::Ident(ident), Some(TokenTree::Punct(punct)))
+                if ident == "error" && punct.as_char() == '=' =>
+            {
+                let ty = arg[2..].iter().cloned().collect::<proc_macro2::TokenStream>();
+                error_type = Some(syn::parse2::<syn::Type>(ty)?);
+            }
+            _ => {
+                if !remaining.is_empty() {
+                    remaining.extend(quote! { , });
+                }
+                remaining.extend(arg);
+            }
+        }
+    }
+    Ok((remaining.into(), error_type))
+}
+
+// EnumName, VariantName and TupleVariableName of a state or region path.
+pub type Path = [Option<String>; 3];
+
+pub fn parse_attributes(args: &AttributeArgs) -> (Path, Path, Option<String>, bool, u32, Path) {
     let mut state_path = [None, None, None];
     let mut region_path = [None, None, None];
     let mut storage_type = None;
+    let mut events = false;
+    let mut history = 0;
+    let mut timeout_path = [None, None, None];
 
     for arg in args {
         match arg {
             NestedMeta::Meta(Meta::NameValue(nv)) => match nv.path.get_ident() {
                 Some(ident) if ident == "state" => state_path = parse_path(&nv.lit),
                 Some(ident) if ident == "region" => region_path = parse_path(&nv.lit),
+                Some(ident) if ident == "timeout" => timeout_path = parse_path(&nv.lit),
                 Some(ident) if ident == "storage" => {
                     if let Lit::Str(lit_str) = &nv.lit {
                         storage_type = Some(lit_str.value());
                     }
                 }
+                Some(ident) if ident == "history" => {
+                    if let Lit::Int(lit_int) = &nv.lit {
+                        history = lit_int.base10_parse().expect("Expected history = u32");
+                    }
+                }
+                Some(ident) if ident == "events" => {
+                    if let Lit::Bool(lit_bool) = &nv.lit {
+                        events = lit_bool.value;
+                    }
+                }
                 _ => {}
             },
             _ => {}
         }
     }
 
-    (state_path, region_path, storage_type)
+    (state_path, region_path, storage_type, events, history, timeout_path)
 }
 
 pub fn parse_path(attr: &Lit) -> [Option<String>; 3] {
@@ -237,3 +351,87 @@ pub fn get_storage_type(storage_type_str: &Option<String>) -> Ident {
         _ => format_ident!("Instance"),
     }
 }
+
+// Transition edge `From -> To | OtherTo`.
+struct Transition {
+    from: Ident,
+    to: Punctuated<Ident, Token![|]>,
+}
+
+impl Parse for Transition {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let from = input.parse()?;
+        input.parse::<Token![->]>()?;
+        let to = Punctuated::parse_separated_nonempty(input)?;
+        Ok(Transition { from, to })
+    }
+}
+
+pub fn state_machine_def(attr: TokenStream, input: TokenStream) -> TokenStream {
+    let transitions = parse_macro_input!(attr with Punctuated::<Transition, Token![,]>::parse_terminated);
+    let input_enum = parse_macro_input!(input as ItemEnum);
+    let state_enum = &input_enum.ident;
+
+    if let Err(error) = check_transitions(&input_enum, &transitions) {
+        let error = error.to_compile_error();
+        return quote! { #input_enum #error }.into();
+    }
+
+    // Invoke the transitions! macro (soroban-tools).
+    let edges = transitions.iter().map(|transition| {
+        let from = &transition.from;
+        let to = transition.to.iter();
+        quote! { #from -> #(#to)|* }
+    });
+    let output = quote! {
+        #input_enum
+        soroban_tools::transitions!(#state_enum; #(#edges),*);
+    };
+    output.into()
+}
+
+// Edges must use declared variants, and every variant must be reachable
+// from the initial state (source of the first edge).
+fn check_transitions(
+    input_enum: &ItemEnum,
+    transitions: &Punctuated<Transition, Token![,]>,
+) -> syn::Result<()> {
+    let variants: Vec<&Ident> = input_enum.variants.iter().map(|v| &v.ident).collect();
+    let Some(initial) = transitions.first().map(|t| &t.from) else {
+        return Err(syn::Error::new_spanned(
+            &input_enum.ident,
+            "Expected at least one transition (e.g., #[state_machine_def(Start -> End)])",
+        ));
+    };
+
+    for transition in transitions {
+        for state in core::iter::once(&transition.from).chain(transition.to.iter()) {
+            if !variants.contains(&state) {
+                return Err(syn::Error::new_spanned(
+                    state,
+                    format!("`{}` is not a variant of `{}`", state, input_enum.ident),
+                ));
+            }
+        }
+    }
+
+    let mut reachable = vec![initial];
+    let mut index = 0;
+    while let Some(&state) = reachable.get(index) {
+        for transition in transitions.iter().filter(|t| &t.from == state) {
+            for to in transition.to.iter() {
+                if !reachable.contains(&to) {
+                    reachable.push(to);
+                }
+            }
+        }
+        index += 1;
+    }
+    match variants.iter().find(|variant| !reachable.contains(variant)) {
+        Some(variant) => Err(syn::Error::new_spanned(
+            variant,
+            format!("`{}` is unreachable from the initial state `{}`", variant, initial),
+        )),
+        None => Ok(()),
+    }
+}
@@ -53,12 +53,24 @@ pub fn state_machine(attr: TokenStream, input: TokenStream) -> TokenStream {
     fsm::state_machine(attr, input)
 }
 
+#[cfg(feature = "state-machine")]
+#[proc_macro_attribute]
+pub fn state_machine_def(attr: TokenStream, input: TokenStream) -> TokenStream {
+    fsm::state_machine_def(attr, input)
+}
+
 #[cfg(feature = "state-machine")]
 #[proc_macro_derive(TransitionHandler)]
 pub fn transition_handler_derive(input: TokenStream) -> TokenStream {
     fsm::transition_handler_derive(input)
 }
 
+#[cfg(feature = "state-machine")]
+#[proc_macro_derive(FallibleTransitionHandler)]
+pub fn fallible_transition_handler_derive(input: TokenStream) -> TokenStream {
+    fsm::fallible_transition_handler_derive(input)
+}
+
 #[cfg(feature = "storage")]
 #[proc_macro_attribute]
 pub fn storage(attr: TokenStream, input: TokenStream) -> TokenStream {
@@ -71,6 +83,12 @@ pub fn key_constraint(attr: TokenStream, input: TokenStream) -> TokenStream {
     storage::key_constraint(attr, input)
 }
 
+#[cfg(feature = "storage")]
+#[proc_macro_derive(StorageKey, attributes(key))]
+pub fn storage_key_derive(input: TokenStream) -> TokenStream {
+    storage::storage_key_derive(input)
+}
+
 #[cfg(feature = "circuit-breaker")]
 #[proc_macro_attribute]
 pub fn when_opened(attr: TokenStream, input: TokenStream) -> TokenStream {
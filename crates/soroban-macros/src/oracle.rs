@@ -21,7 +21,7 @@ fn oracle_broker_trait<K: quote::ToTokens, V: quote::ToTokens>(
     v_ty: V,
 ) -> proc_macro2::TokenStream {
     quote! {
-        fn subscribe(env: Env, topic: #k_ty, envelope: soroban_kit::oracle::Envelope) -> Option<#v_ty>;
+        fn subscribe(env: Env, topic: #k_ty, envelope: soroban_tools::oracle::Envelope) -> Option<#v_ty>;
         fn publish(env: Env, topic: #k_ty, publisher: Address, data: #v_ty);
     }
 }
@@ -33,7 +33,7 @@ fn oracle_subscriber_trait<K: quote::ToTokens, V: quote::ToTokens>(
 ) -> proc_macro2::TokenStream {
     quote! {
         fn request(env: Env, topic: #k_ty, subscriber: Address, broker: Address) -> Option<#v_ty>;
-        fn receive(env: Env, topic: #k_ty, envelope: soroban_kit::oracle::Envelope, data: #v_ty);
+        fn receive(env: Env, topic: #k_ty, envelope: soroban_tools::oracle::Envelope, data: #v_ty);
     }
 }
 
@@ -69,18 +69,19 @@ pub fn oracle_subscriber_attribute(args: TokenStream, input: TokenStream) -> Tok
             fn request(
                 env: Env, topic: #k_ty, subscriber: Address, broker: Address
             ) -> Option<#v_ty> {
-                assert_ne!(env.current_contract_address(), subscriber);
-                assert_ne!(env.current_contract_address(), broker);
-                let envelope = soroban_kit::oracle::Envelope {
+                if env.current_contract_address() == subscriber || env.current_contract_address() == broker {
+                    soroban_sdk::panic_with_error!(&env, soroban_tools::error::KitError::InvalidEnvelope);
+                }
+                let envelope = soroban_tools::oracle::Envelope {
                     subscriber,
                     broker: broker.clone(),
                     router: env.current_contract_address(),
                 };
-                <#ty as soroban_kit::oracle::Events<#k_ty, #v_ty>>::on_request(
+                <#ty as soroban_tools::oracle::Events<#k_ty, #v_ty>>::on_request(
                     &env, &topic, &envelope,
                 );
                 if let Some(data) = #broker_trait_client::new(&env, &broker).subscribe(&topic, &envelope) {
-                    <#ty as soroban_kit::oracle::Events<#k_ty, #v_ty>>::on_sync_receive
+                    <#ty as soroban_tools::oracle::Events<#k_ty, #v_ty>>::on_sync_receive
                         (&env, &topic, &envelope, &data);
                     Some(data)
                 } else {
@@ -90,12 +91,13 @@ pub fn oracle_subscriber_attribute(args: TokenStream, input: TokenStream) -> Tok
             fn receive(
                 env: Env,
                 topic: #k_ty,
-                envelope: soroban_kit::oracle::Envelope,
+                envelope: soroban_tools::oracle::Envelope,
                 data: #v_ty
             ) {
-                assert_ne!(env.current_contract_address(), envelope.subscriber);
-                assert_ne!(env.current_contract_address(), envelope.broker);
-                <#ty as soroban_kit::oracle::Events<#k_ty, #v_ty>>::on_async_receive(
+                if env.current_contract_address() == envelope.subscriber || env.current_contract_address() == envelope.broker {
+                    soroban_sdk::panic_with_error!(&env, soroban_tools::error::KitError::InvalidEnvelope);
+                }
+                <#ty as soroban_tools::oracle::Events<#k_ty, #v_ty>>::on_async_receive(
                     &env, &topic, &envelope, &data
                 );
             }
@@ -133,17 +135,18 @@ pub fn oracle_broker_attribute(args: TokenStream, input: TokenStream) -> TokenSt
         #[contractimpl]
         impl #broker_trait for #ty {
             fn subscribe(
-                env: Env, topic: #k_ty, envelope: soroban_kit::oracle::Envelope
+                env: Env, topic: #k_ty, envelope: soroban_tools::oracle::Envelope
             ) -> Option<#v_ty> {
-                assert_ne!(env.current_contract_address(), envelope.subscriber);
-                assert_ne!(env.current_contract_address(), envelope.router);
-                <#ty as soroban_kit::oracle::Events<#k_ty, #v_ty>>::on_subscribe
+                if env.current_contract_address() == envelope.subscriber || env.current_contract_address() == envelope.router {
+                    soroban_sdk::panic_with_error!(&env, soroban_tools::error::KitError::InvalidEnvelope);
+                }
+                <#ty as soroban_tools::oracle::Events<#k_ty, #v_ty>>::on_subscribe
                     (&env, &topic, &envelope)
             }
             fn publish(
                 env: Env, topic: #k_ty, publisher: Address, data: #v_ty
             ) {
-                let envelopes = <#ty as soroban_kit::oracle::Events<#k_ty, #v_ty>>::on_publish(
+                let envelopes = <#ty as soroban_tools::oracle::Events<#k_ty, #v_ty>>::on_publish(
                     &env, &topic, &data, &publisher,
                 );
                 envelopes.iter().for_each(|envelope| {
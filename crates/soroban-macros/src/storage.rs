@@ -12,38 +12,117 @@
 
 extern crate proc_macro;
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::DeriveInput;
-use syn::{parse_macro_input, AttributeArgs, ItemStruct};
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, AttributeArgs, Item, ItemStruct, Lit, Meta, NestedMeta, Token};
+use syn::{Attribute, DeriveInput, Fields, Generics, Ident, Path, Type};
 
 #[allow(unused_imports)]
 use soroban_tools::impl_key_constraint;
 #[allow(unused_imports)]
 use soroban_tools::impl_storage;
 
+// Flag options of the storage attribute.
+const STORAGE_FLAGS: [&str; 3] = ["events", "remove_expired", "packed"];
+
+// Storage attribute argument, also accepting types as named values
+// (e.g. `migrate_from = DataV1`).
+enum StorageArg {
+    Meta(NestedMeta),
+    Type(Ident, Box<Type>),
+}
+
+impl Parse for StorageArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(Ident) && input.peek2(Token![=]) && !input.peek3(Lit) {
+            let ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            Ok(StorageArg::Type(ident, Box::new(input.parse()?)))
+        } else {
+            Ok(StorageArg::Meta(input.parse()?))
+        }
+    }
+}
+
+struct StorageArgs(Vec<StorageArg>);
+
+impl Parse for StorageArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let args = Punctuated::<StorageArg, Token![,]>::parse_terminated(input)?;
+        Ok(StorageArgs(args.into_iter().collect()))
+    }
+}
+
 pub fn storage(attr: TokenStream, input: TokenStream) -> TokenStream {
-    let args = parse_macro_input!(attr as AttributeArgs);
-    let input = parse_macro_input!(input as ItemStruct);
+    let StorageArgs(storage_args) = parse_macro_input!(attr as StorageArgs);
+    let (mut args, mut type_args): (AttributeArgs, Vec<(Ident, Box<Type>)>) = (Vec::new(), Vec::new());
+    for arg in storage_args {
+        match arg {
+            StorageArg::Meta(meta) => args.push(meta),
+            StorageArg::Type(ident, ty) => type_args.push((ident, ty)),
+        }
+    }
+    let input = parse_macro_input!(input as Item);
+    let (ident, generics, attrs, fields) = match &input {
+        Item::Struct(item) => (&item.ident, &item.generics, &item.attrs, Some(&item.fields)),
+        Item::Enum(item) => (&item.ident, &item.generics, &item.attrs, None),
+        _ => panic!("Expected a struct or an enum"),
+    };
     let storage_type = match args.first() {
         Some(syn::NestedMeta::Meta(syn::Meta::Path(p))) => quote! { #p },
         _ => unimplemented!(),
     };
 
-    let trait_ident = args.get(1).and_then(|arg| {
-        if let syn::NestedMeta::Meta(syn::Meta::Path(p)) = arg {
-            Some(quote! { , #p })
-        } else {
-            None
-        }
+    let trait_ident = args.get(1).and_then(|arg| match arg {
+        syn::NestedMeta::Meta(syn::Meta::Path(p)) if is_key_constraint(p) => Some(quote! { , #p }),
+        _ => None,
+    });
+
+    // Structs deriving StorageKey are constrained to their generated key.
+    let trait_ident = trait_ident.or_else(|| {
+        has_storage_key_derive(attrs).then(|| {
+            let key_trait = format_ident!("{}KeyConstraint", ident);
+            quote! { , #key_trait }
+        })
     });
 
+    let options = parse_options(&args, &type_args);
+    let queries = index_queries(&args, ident, generics, fields);
+    let packed = args
+        .iter()
+        .any(|arg| matches!(arg, NestedMeta::Meta(Meta::Path(path)) if path.is_ident("packed")))
+        .then(|| {
+            if !generics.params.is_empty() {
+                panic!("Generic types cannot be packed");
+            }
+            if attrs.iter().any(|attr| {
+                attr.path.segments.last().is_some_and(|segment| segment.ident == "contracttype")
+            }) {
+                panic!("Packed types replace the #[contracttype] encoding");
+            }
+            packed_impl(ident, fields.expect("Packed types must be structs"))
+        });
+
     // Invoke the impl_storage! macro (soroban-tools).
-    let struct_ident = &input.ident;
-    let expanded = quote! {
-        soroban_tools::impl_storage!(#storage_type, #struct_ident #trait_ident);
+    let expanded = if generics.params.is_empty() {
+        quote! {
+            soroban_tools::impl_storage!(#storage_type, #ident #trait_ident #options);
+            #queries
+            #packed
+        }
+    } else {
+        // Type parameters and where-clauses are forwarded to the generated impls.
+        let (_, ty_generics, _) = generics.split_for_impl();
+        let (params, predicates) = generic_bounds(generics);
+        quote! {
+            soroban_tools::impl_storage!([#params] #storage_type, #ident #ty_generics
+                #trait_ident where [#predicates] #options);
+            #queries
+        }
     };
 
-    // Return original struct combined with storage impl.
+    // Return original item combined with storage impl.
     let output = quote! {
         #input
         #expanded
@@ -51,22 +130,418 @@ pub fn storage(attr: TokenStream, input: TokenStream) -> TokenStream {
     output.into()
 }
 
+// Key constraints are traits, unlike the lowercase storage options.
+fn is_key_constraint(path: &Path) -> bool {
+    !STORAGE_FLAGS.iter().any(|flag| path.is_ident(flag))
+        && path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident.to_string().starts_with(char::is_uppercase))
+}
+
+// Split generics into impl_storage! / impl_key_constraint! type parameters and
+// where-clause predicates (with trailing commas).
+fn generic_bounds(generics: &Generics) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let params = generics.params.iter();
+    let predicates = generics
+        .where_clause
+        .iter()
+        .flat_map(|where_clause| where_clause.predicates.iter());
+    (quote! { #(#params,)* }, quote! { #(#predicates,)* })
+}
+
+// Convert the named storage options into impl_storage! options.
+fn parse_options(args: &AttributeArgs, type_args: &[(Ident, Box<Type>)]) -> proc_macro2::TokenStream {
+    let mut ttl = None;
+    let mut extend_on = None;
+    let mut version = None;
+    let mut migrate_from = None;
+    let mut expires_after = None;
+    let mut remove_expired = false;
+    let mut options = Vec::new();
+
+    for (position, arg) in args.iter().enumerate() {
+        match arg {
+            NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("ttl") => {
+                let (mut threshold, mut extend_to) = (None, None);
+                for nested in &list.nested {
+                    match nested {
+                        NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("threshold") => {
+                            threshold = Some(nv.lit.clone())
+                        }
+                        NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("extend_to") => {
+                            extend_to = Some(nv.lit.clone())
+                        }
+                        _ => panic!("Expected ttl(threshold = .., extend_to = ..)"),
+                    }
+                }
+                match (threshold, extend_to) {
+                    (Some(threshold), Some(extend_to)) => ttl = Some((threshold, extend_to)),
+                    _ => panic!("Expected ttl(threshold = .., extend_to = ..)"),
+                }
+            }
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("extend_on") => {
+                extend_on = match &nv.lit {
+                    Lit::Str(lit_str) => Some(
+                        lit_str
+                            .value()
+                            .split('|')
+                            .map(|event| match event.trim() {
+                                "read" => format_ident!("read"),
+                                "write" => format_ident!("write"),
+                                _ => panic!("Expected extend_on = \"read|write\""),
+                            })
+                            .collect::<Vec<_>>(),
+                    ),
+                    _ => panic!("Expected extend_on = \"read|write\""),
+                }
+            }
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("events") => {
+                options.push(quote! { events });
+            }
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("remove_expired") => {
+                remove_expired = true;
+            }
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("expires_after") => {
+                expires_after = match &nv.lit {
+                    Lit::Int(lit_int) if lit_int.base10_parse::<u32>().is_ok() => {
+                        Some(lit_int.clone())
+                    }
+                    _ => panic!("Expected expires_after = u32"),
+                }
+            }
+            NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("index") => {
+                let fields = index_fields(list);
+                options.push(quote! { index(#(#fields),*) });
+            }
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("version") => {
+                version = match &nv.lit {
+                    // Version 0 is reserved for untagged data.
+                    Lit::Int(lit_int) if lit_int.base10_parse::<u32>().is_ok_and(|v| v > 0) => {
+                        Some(lit_int.clone())
+                    }
+                    _ => panic!("Expected version = u32 (greater than 0)"),
+                }
+            }
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("packed") => {}
+            // Storage tier and key constraint.
+            NestedMeta::Meta(Meta::Path(_)) if position == 0 => {}
+            NestedMeta::Meta(Meta::Path(path)) if position == 1 && is_key_constraint(path) => {}
+            _ => panic!("Unexpected storage option {}", quote! { #arg }),
+        }
+    }
+
+    for (ident, ty) in type_args {
+        match ident.to_string().as_str() {
+            "migrate_from" => migrate_from = Some(ty),
+            _ => panic!("Unexpected storage option {}", ident),
+        }
+    }
+
+    match (ttl, extend_on) {
+        (Some((threshold, extend_to)), None) => options.push(quote! {
+            ttl(threshold = #threshold, extend_to = #extend_to)
+        }),
+        (Some((threshold, extend_to)), Some(events)) => options.push(quote! {
+            ttl(threshold = #threshold, extend_to = #extend_to, extend_on = #(#events)|*)
+        }),
+        (None, Some(_)) => panic!("extend_on requires a ttl policy"),
+        (None, None) => {}
+    }
+    match (expires_after, remove_expired) {
+        (Some(after), false) => options.push(quote! { expiry(after = #after) }),
+        (Some(after), true) => options.push(quote! { expiry(after = #after, remove_expired) }),
+        (None, true) => panic!("remove_expired requires expires_after"),
+        (None, false) => {}
+    }
+    match (version, migrate_from) {
+        (Some(version), None) => options.push(quote! { version = #version }),
+        (Some(version), Some(from)) => options.push(quote! {
+            version = #version, migrate_from = #from
+        }),
+        (None, Some(_)) => panic!("migrate_from requires a version"),
+        (None, None) => {}
+    }
+
+    if options.is_empty() {
+        quote! {}
+    } else {
+        quote! { ; #(#options),* }
+    }
+}
+
+fn index_fields(list: &syn::MetaList) -> Vec<&Ident> {
+    list.nested
+        .iter()
+        .map(|nested| match nested {
+            NestedMeta::Meta(Meta::Path(path)) if path.get_ident().is_some() => {
+                path.get_ident().unwrap()
+            }
+            _ => panic!("Expected index(field, ..)"),
+        })
+        .collect()
+}
+
+// Generate the find_by_<field> queries of the indexed fields.
+fn index_queries(
+    args: &AttributeArgs,
+    ident: &Ident,
+    generics: &Generics,
+    fields: Option<&Fields>,
+) -> proc_macro2::TokenStream {
+    let queries = args
+        .iter()
+        .filter_map(|arg| match arg {
+            NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("index") => {
+                Some(index_fields(list))
+            }
+            _ => None,
+        })
+        .flatten()
+        .map(|field| {
+            let ty = fields
+                .expect("Indexes require a struct")
+                .iter()
+                .find(|f| f.ident.as_ref() == Some(field))
+                .map(|f| &f.ty)
+                .unwrap_or_else(|| panic!("Unknown index field {}", field));
+            let query = format_ident!("find_by_{}", field);
+            let name = field.to_string();
+            quote! {
+                /// Find the keys of the stored data by index.
+                pub fn #query<K>(env: &soroban_sdk::Env, #field: &#ty) -> soroban_sdk::Vec<K>
+                where
+                    K: soroban_sdk::IntoVal<soroban_sdk::Env, soroban_sdk::Val>
+                        + soroban_sdk::TryFromVal<soroban_sdk::Env, soroban_sdk::Val>,
+                    for<'a> soroban_tools::storage::StorageProxy<'a, K, Self>:
+                        soroban_tools::storage::StorageOps<Self>,
+                {
+                    soroban_tools::storage::find_by_index::<Self, K, _>(env, #name, #field)
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if queries.is_empty() {
+        quote! {}
+    } else {
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        quote! {
+            impl #impl_generics #ident #ty_generics #where_clause {
+                #(#queries)*
+            }
+        }
+    }
+}
+
+// Generate the packed encoding of the struct (bool fields as bit flags
+// followed by the fixed-width fields) and its Val conversions.
+fn packed_impl(ident: &Ident, fields: &Fields) -> proc_macro2::TokenStream {
+    let members: Vec<_> = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match &field.ident {
+            Some(ident) => (quote! { #ident }, &field.ty),
+            None => {
+                let index = syn::Index::from(index);
+                (quote! { #index }, &field.ty)
+            }
+        })
+        .collect();
+    let is_flag = |ty: &Type| matches!(ty, Type::Path(path) if path.path.is_ident("bool"));
+    let flag_count = members.iter().filter(|(_, ty)| is_flag(ty)).count();
+    let flag_bytes = flag_count.div_ceil(8);
+
+    let (mut flags, mut writes, mut reads) = (Vec::new(), Vec::new(), Vec::new());
+    let mut offset = quote! { #flag_bytes as u32 };
+    for (member, ty) in &members {
+        if is_flag(ty) {
+            let (byte, bit) = (flags.len() / 8, (flags.len() % 8) as u8);
+            flags.push(quote! {
+                if self.#member {
+                    flags[#byte] |= 1 << #bit;
+                }
+            });
+            reads.push(quote! { #member: flags[#byte] & (1 << #bit) != 0 });
+        } else {
+            writes.push(quote! {
+                soroban_tools::storage::PackedField::write(&self.#member, &mut bytes);
+            });
+            reads.push(quote! {
+                #member: <#ty as soroban_tools::storage::PackedField>::read(bytes, #offset)
+            });
+            offset = quote! { #offset + <#ty as soroban_tools::storage::PackedField>::SIZE };
+        }
+    }
+    let (flags_mutability, mutability) = (
+        (!flags.is_empty()).then(|| quote! { mut }),
+        (!writes.is_empty()).then(|| quote! { mut }),
+    );
+
+    quote! {
+        impl soroban_tools::storage::Packed for #ident {
+            const SIZE: u32 = #offset;
+
+            fn pack(&self, env: &soroban_sdk::Env) -> soroban_sdk::Bytes {
+                let #flags_mutability flags = [0u8; #flag_bytes];
+                #(#flags)*
+                let #mutability bytes = soroban_sdk::Bytes::from_array(env, &flags);
+                #(#writes)*
+                bytes
+            }
+
+            fn unpack(bytes: &soroban_sdk::Bytes) -> Option<Self> {
+                if bytes.len() != Self::SIZE {
+                    return None;
+                }
+                let mut flags = [0u8; #flag_bytes];
+                bytes.slice(0..#flag_bytes as u32).copy_into_slice(&mut flags);
+                Some(Self {
+                    #(#reads),*
+                })
+            }
+        }
+
+        impl soroban_sdk::TryFromVal<soroban_sdk::Env, #ident> for soroban_sdk::Val {
+            type Error = soroban_sdk::ConversionError;
+
+            fn try_from_val(
+                env: &soroban_sdk::Env,
+                data: &#ident,
+            ) -> Result<Self, soroban_sdk::ConversionError> {
+                let bytes = soroban_tools::storage::Packed::pack(data, env);
+                Ok(soroban_sdk::IntoVal::into_val(&bytes, env))
+            }
+        }
+
+        impl soroban_sdk::TryFromVal<soroban_sdk::Env, soroban_sdk::Val> for #ident {
+            type Error = soroban_sdk::ConversionError;
+
+            fn try_from_val(
+                env: &soroban_sdk::Env,
+                val: &soroban_sdk::Val,
+            ) -> Result<Self, soroban_sdk::ConversionError> {
+                let bytes = <soroban_sdk::Bytes as soroban_sdk::TryFromVal<_, _>>::try_from_val(
+                    env, val,
+                )
+                .map_err(|_| soroban_sdk::ConversionError)?;
+                soroban_tools::storage::Packed::unpack(&bytes).ok_or(soroban_sdk::ConversionError)
+            }
+        }
+    }
+}
+
+fn has_storage_key_derive(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path.is_ident("derive")
+            && matches!(attr.parse_meta(), Ok(Meta::List(list)) if list.nested.iter().any(|nested| {
+                matches!(nested, NestedMeta::Meta(Meta::Path(path))
+                    if path.segments.last().is_some_and(|segment| segment.ident == "StorageKey"))
+            }))
+    })
+}
+
+pub fn storage_key_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as ItemStruct);
+    let struct_ident = &input.ident;
+    let vis = &input.vis;
+    let key_ident = format_ident!("{}Key", struct_ident);
+    let key_trait = format_ident!("{}KeyConstraint", struct_ident);
+
+    // Fields annotated with #[key] make up the key variant.
+    let key_fields: Vec<_> = input
+        .fields
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| field.attrs.iter().any(|attr| attr.path.is_ident("key")))
+        .map(|(index, field)| {
+            let member = match &field.ident {
+                Some(ident) => quote! { #ident },
+                None => {
+                    let index = syn::Index::from(index);
+                    quote! { #index }
+                }
+            };
+            (member, &field.ty)
+        })
+        .collect();
+
+    let (variant, value) = if key_fields.is_empty() {
+        (
+            quote! { #struct_ident },
+            quote! { #key_ident::#struct_ident },
+        )
+    } else {
+        let types = key_fields.iter().map(|(_, ty)| ty);
+        let members = key_fields.iter().map(|(member, _)| member);
+        (
+            quote! { #struct_ident(#(#types),*) },
+            quote! { #key_ident::#struct_ident(#(self.#members.clone()),*) },
+        )
+    };
+
+    // Generate the companion key type and its constraint (soroban-tools).
+    let expanded = quote! {
+        #[soroban_sdk::contracttype]
+        #[derive(Clone, Debug, Eq, PartialEq)]
+        #vis enum #key_ident {
+            #variant,
+        }
+
+        soroban_tools::impl_key_constraint!(#key_ident, #key_trait);
+
+        impl #struct_ident {
+            /// Storage key derived from the #[key] fields.
+            #vis fn storage_key(&self) -> #key_ident {
+                #value
+            }
+        }
+    };
+    expanded.into()
+}
+
 pub fn key_constraint(attr: TokenStream, input: TokenStream) -> TokenStream {
     let args = parse_macro_input!(attr as AttributeArgs);
     let input = parse_macro_input!(input as DeriveInput);
 
-    assert_eq!(args.len(), 1);
-
-    let key_trait = match &args[0] {
-        syn::NestedMeta::Meta(syn::Meta::Path(path)) => quote! { #path },
+    let key_trait = match args.first() {
+        Some(syn::NestedMeta::Meta(syn::Meta::Path(path))) => quote! { #path },
         _ => panic!("Expected a trait"),
     };
 
+    // Keys holding data can be recorded in a registry (see storage::keys)
+    // and keys can be hashed (see storage::HashedKey).
+    let (mut registry, mut hashed) = (false, false);
+    for arg in args.iter().skip(1) {
+        match arg {
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("registry") => registry = true,
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("hashed") => hashed = true,
+            _ => panic!("Expected #[key_constraint(Trait[, hashed][, registry])]"),
+        }
+    }
+
     let type_ident = &input.ident;
 
     // Invoke the impl_key_constraint! macro (soroban-tools).
-    let expanded = quote! {
-        soroban_tools::impl_key_constraint!(#type_ident, #key_trait);
+    let expanded = if input.generics.params.is_empty() {
+        let options = match (hashed, registry) {
+            (true, true) => quote! { ; hashed, registry },
+            (true, false) => quote! { ; hashed },
+            (false, true) => quote! { ; registry },
+            (false, false) => quote! {},
+        };
+        quote! {
+            soroban_tools::impl_key_constraint!(#type_ident, #key_trait #options);
+        }
+    } else if registry || hashed {
+        panic!("Generic key types cannot be registered or hashed");
+    } else {
+        let (_, ty_generics, _) = input.generics.split_for_impl();
+        let (params, predicates) = generic_bounds(&input.generics);
+        quote! {
+            soroban_tools::impl_key_constraint!([#params] #type_ident #ty_generics, #key_trait
+                where [#predicates]);
+        }
     };
 
     // Return original struct combined with storage impl.
@@ -19,11 +19,17 @@ mod tests {
 
     use core::panic::AssertUnwindSafe;
     use soroban_sdk::{
-        contract, contractimpl, contracttype, testutils::Address as _, Address, Env,
+        contract, contracterror, contractimpl, contracttype,
+        testutils::{Address as _, Ledger},
+        vec, Address, Env, Symbol,
     };
 
-    use soroban_macros::{state_machine, TransitionHandler};
-    use soroban_tools::{fsm, fsm::{StateMachine, TransitionHandler}};
+    use soroban_macros::{state_machine, state_machine_def, FallibleTransitionHandler, TransitionHandler};
+    use soroban_tools::{
+        error::KitError,
+        fsm,
+        fsm::{FallibleTransitionHandler, StateMachine, TransitionHandler},
+    };
 
     use std::panic::catch_unwind;
 
@@ -49,6 +55,115 @@ mod tests {
         Private(Address),
     }
 
+    // Legal transitions between job states.
+    #[contracttype]
+    #[state_machine_def(
+        Idle -> Running,
+        Running -> Paused | Ended,
+        Paused -> Running | Ended,
+    )]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub enum Job {
+        Idle,
+        Running(u32),
+        Paused,
+        Ended,
+    }
+
+    #[derive(TransitionHandler)]
+    pub struct JobRunner;
+
+    impl JobRunner {
+        fn on_effect(&self, env: &Env, state_machine: &StateMachine<Room, Job>) {
+            // Published since the state machine is configured with events = true.
+            state_machine.transition(env, &Job::Running(1));
+        }
+
+        #[state_machine(state = "Job:Idle", region = "Room:Public", events = true)]
+        fn start(&self, env: &Env) {}
+    }
+
+    #[derive(TransitionHandler)]
+    pub struct Scheduler;
+
+    impl Scheduler {
+        fn on_effect(&self, env: &Env, state_machine: &StateMachine<Room, Job>) {
+            let next = match state_machine.get_state(env).unwrap() {
+                Job::Running(_) => Job::Paused,
+                _ => Job::Running(env.ledger().sequence()),
+            };
+            state_machine.transition(env, &next);
+        }
+
+        // The last 3 state changes of the account are recorded.
+        #[state_machine(state = "Job:Running:id", region = "Room:Private:account",
+            storage = "persistent", history = 3)]
+        fn pause(&self, env: &Env, account: &Address, id: &u32) {}
+
+        #[state_machine(state = "Job:Paused", region = "Room:Private:account",
+            storage = "persistent", history = 3)]
+        fn resume(&self, env: &Env, account: &Address) {}
+    }
+
+    #[derive(TransitionHandler)]
+    pub struct Auction;
+
+    impl Auction {
+        // Panics with KitError::StateExpired after the deadline of State:Opened.
+        #[state_machine(state = "State:Opened", region = "Room:Public", timeout = "State:Ready")]
+        fn bid(&self, env: &Env) {}
+
+        // Moves to State:Ready when invoked after the deadline of State:Opened.
+        #[state_machine(state = "State:Ready", region = "Room:Public", timeout = "State:Ready")]
+        fn settle(&self, env: &Env) {}
+    }
+
+    #[contracterror]
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    pub enum VaultError {
+        Locked = 1,
+        NotOpened = 2,
+        NotFound = 3,
+        Expired = 4,
+    }
+
+    impl From<KitError> for VaultError {
+        fn from(error: KitError) -> Self {
+            match error {
+                KitError::StateMismatch => VaultError::NotOpened,
+                KitError::StateExpired => VaultError::Expired,
+                _ => VaultError::NotFound,
+            }
+        }
+    }
+
+    #[derive(FallibleTransitionHandler)]
+    pub struct Vault;
+
+    impl Vault {
+        fn on_guard(&self, env: &Env, _state_machine: &StateMachine<Room, State>) -> Result<(), VaultError> {
+            // Guard failures are returned to the caller instead of trapping
+            // (e.g., the vault is locked while a game is being played).
+            match env.storage().instance().has(&Game::WorldOfWarcraft) {
+                true => Err(VaultError::Locked),
+                false => Ok(()),
+            }
+        }
+
+        // Returns VaultError::NotOpened when the vault state is not State:Opened.
+        #[state_machine(state = "State:Opened", region = "Room:Private:account", error = VaultError)]
+        fn withdraw(&self, env: &Env, account: &Address, amount: u32) -> Result<u32, VaultError> {
+            Ok(amount)
+        }
+
+        // Fails with VaultError::Expired after the deadline of State:Opened.
+        #[state_machine(state = "State:Opened", region = "Room:Private:account",
+            timeout = "State:Ready", error = VaultError)]
+        fn deposit(&self, env: &Env, account: &Address, amount: u32) -> Result<u32, VaultError> {
+            Ok(amount)
+        }
+    }
+
     #[derive(TransitionHandler)]
     pub struct GamingLobby;
 
@@ -151,9 +266,256 @@ mod tests {
             gaming_lobby.quit(&env, &player2, &Game::LeagueOfLegends);
 
             gaming_lobby.close(&env);
+
+            // The lobby state has been removed.
+            let state_machine =
+                StateMachine::<Room, State>::new(&Room::Public, fsm::StorageType::Instance);
+            assert_eq!(state_machine.try_get_state(&env), Err(KitError::StateNotFound));
+        }
+    }
+
+    #[contractimpl]
+    impl TestContract {
+        pub fn test_transitions(env: Env) {
+            let job = StateMachine::<Room, Job>::new(&Room::Public, fsm::StorageType::Instance);
+            assert_eq!(job.try_transition(&env, &Job::Running(1)), Err(KitError::StateNotFound));
+            job.set_state(&env, &Job::Idle);
+
+            // Edges are declared between variants, regardless of their tuple values.
+            assert_eq!(job.try_transition(&env, &Job::Ended), Err(KitError::InvalidTransition));
+            assert_eq!(job.try_transition(&env, &Job::Running(1)), Ok(()));
+            assert_eq!(job.try_transition(&env, &Job::Running(2)), Err(KitError::InvalidTransition));
+            assert_eq!(job.get_state(&env), Some(Job::Running(1)));
+            job.transition(&env, &Job::Paused);
+            job.transition(&env, &Job::Running(2));
+            job.transition(&env, &Job::Ended);
+
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                job.transition(&env, &Job::Idle);
+            }));
+            assert!(result.is_err(), "The operation should panic. Ended is final");
+            assert_eq!(job.get_state(&env), Some(Job::Ended));
+        }
+    }
+
+    #[contractimpl]
+    impl TestContract {
+        pub fn test_fallible_state_machine(env: Env) {
+            let account = Address::generate(&env);
+            let vault = Vault;
+            assert_eq!(vault.withdraw(&env, &account, 10), Err(VaultError::NotFound));
+
+            let region = Room::Private(account.clone());
+            let state_machine = StateMachine::<Room, State>::new(&region, fsm::StorageType::Instance);
+            state_machine.set_state(&env, &State::Ready);
+            assert_eq!(vault.withdraw(&env, &account, 10), Err(VaultError::NotOpened));
+
+            state_machine.set_state(&env, &State::Opened);
+            assert_eq!(vault.withdraw(&env, &account, 10), Ok(10));
+
+            env.storage().instance().set(&Game::WorldOfWarcraft, &true);
+            assert_eq!(vault.withdraw(&env, &account, 10), Err(VaultError::Locked));
+        }
+    }
+
+    #[contractimpl]
+    impl TestContract {
+        pub fn test_transition_events(env: Env) {
+            let job = StateMachine::<Room, Job>::new(&Room::Public, fsm::StorageType::Instance);
+            job.set_state(&env, &Job::Idle);
+            JobRunner.start(&env);
+            job.with_events(true).remove_state(&env);
+        }
+    }
+
+    #[contractimpl]
+    impl TestContract {
+        pub fn test_state_timeouts(env: Env) {
+            let auction = Auction;
+            let state_machine =
+                StateMachine::<Room, State>::new(&Room::Public, fsm::StorageType::Instance);
+            let deadline = fsm::Deadline::Ledger(env.ledger().sequence() + 10);
+            state_machine.set_state_with_deadline(&env, &State::Opened, &deadline);
+            assert_eq!(state_machine.get_deadline(&env), Some(deadline));
+            auction.bid(&env);
+
+            // Settling is only allowed after the deadline.
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                auction.settle(&env);
+            }));
+            assert!(result.is_err(), "The operation should panic. The auction is opened");
+
+            env.ledger().with_mut(|li| li.sequence_number += 11);
+            assert!(state_machine.is_expired(&env));
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                auction.bid(&env);
+            }));
+            assert!(result.is_err(), "The operation should panic. The auction is expired");
+            auction.settle(&env);
+            assert_eq!(state_machine.get_state(&env), Some(State::Ready));
+            assert_eq!(state_machine.get_deadline(&env), None);
+
+            // Setting a new state clears the deadline.
+            let deadline = fsm::Deadline::Timestamp(env.ledger().timestamp());
+            state_machine.set_state_with_deadline(&env, &State::Opened, &deadline);
+            state_machine.set_state(&env, &State::Opened);
+            env.ledger().with_mut(|li| li.timestamp += 1);
+            assert!(!state_machine.is_expired(&env));
+            auction.bid(&env);
+        }
+    }
+
+    #[contractimpl]
+    impl TestContract {
+        pub fn open_vault(env: Env, account: Address, ledgers: u32) {
+            let deadline = fsm::Deadline::Ledger(env.ledger().sequence() + ledgers);
+            StateMachine::<Room, State>::new(&Room::Private(account), fsm::StorageType::Instance)
+                .set_state_with_deadline(&env, &State::Opened, &deadline);
+        }
+
+        pub fn check_vault_timeout(env: Env, account: Address) -> bool {
+            StateMachine::<Room, State>::new(&Room::Private(account), fsm::StorageType::Instance)
+                .check_timeout(&env, &State::Ready)
+        }
+
+        pub fn deposit(env: Env, account: Address, amount: u32) -> Result<u32, VaultError> {
+            Vault.deposit(&env, &account, amount)
         }
     }
 
+    #[contractimpl]
+    impl TestContract {
+        pub fn test_transition_history(env: Env) {
+            let account = Address::generate(&env);
+            let region = Room::Private(account.clone());
+            let state_machine = StateMachine::<Room, Job>::new(&region, fsm::StorageType::Persistent)
+                .with_history(3);
+            let scheduler = Scheduler;
+            let sequence = env.ledger().sequence();
+            let record = |from, to, function: Option<&str>| fsm::TransitionRecord {
+                from,
+                to,
+                ledger: sequence,
+                function: function.map(|function| Symbol::new(&env, function)),
+            };
+
+            state_machine.set_state(&env, &Job::Running(1));
+            scheduler.pause(&env, &account, &1);
+            scheduler.resume(&env, &account);
+            assert_eq!(
+                state_machine.history(&env, 10),
+                vec![
+                    &env,
+                    record(Some(Job::Paused), Some(Job::Running(sequence)), Some("resume")),
+                    record(Some(Job::Running(1)), Some(Job::Paused), Some("pause")),
+                    record(None, Some(Job::Running(1)), None),
+                ]
+            );
+
+            // Only the last 3 changes are kept.
+            scheduler.pause(&env, &account, &sequence);
+            assert_eq!(
+                state_machine.history(&env, 10),
+                vec![
+                    &env,
+                    record(Some(Job::Running(sequence)), Some(Job::Paused), Some("pause")),
+                    record(Some(Job::Paused), Some(Job::Running(sequence)), Some("resume")),
+                    record(Some(Job::Running(1)), Some(Job::Paused), Some("pause")),
+                ]
+            );
+            assert_eq!(state_machine.history(&env, 1).len(), 1);
+            state_machine.extend_ttl(&env, 1, 1000);
+
+            // Removing the state is recorded too.
+            state_machine.remove_state(&env);
+            assert_eq!(
+                state_machine.history(&env, 1),
+                vec![&env, record(Some(Job::Paused), None, None)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_macros_state_machine_history() {
+        let env = Env::default();
+        TestContractClient::new(&env, &env.register_contract(None, TestContract))
+            .test_transition_history();
+    }
+
+    #[test]
+    fn test_macros_state_machine_timeouts() {
+        let env = Env::default();
+        TestContractClient::new(&env, &env.register_contract(None, TestContract))
+            .test_state_timeouts();
+    }
+
+    #[test]
+    fn test_macros_state_machine_timeout_check() {
+        let env = Env::default();
+        let contract_id = env.register_contract(None, TestContract);
+        let client = TestContractClient::new(&env, &contract_id);
+        let account = Address::generate(&env);
+        client.open_vault(&account, &10);
+        assert_eq!(client.deposit(&account, &1), 1);
+        assert!(!client.check_vault_timeout(&account));
+        env.ledger().with_mut(|li| li.sequence_number += 11);
+
+        // Depositing after the deadline fails and the timeout state is rolled back.
+        assert_eq!(client.try_deposit(&account, &1), Err(Ok(VaultError::Expired)));
+        let region = Room::Private(account.clone());
+        env.as_contract(&contract_id, || {
+            let state_machine = StateMachine::<Room, State>::new(&region, fsm::StorageType::Instance);
+            assert_eq!(state_machine.get_state(&env), Some(State::Opened));
+            assert!(state_machine.is_expired(&env));
+        });
+
+        // Checking the timeout commits the move.
+        assert!(client.check_vault_timeout(&account));
+        env.as_contract(&contract_id, || {
+            let state_machine = StateMachine::<Room, State>::new(&region, fsm::StorageType::Instance);
+            assert_eq!(state_machine.get_state(&env), Some(State::Ready));
+            assert_eq!(state_machine.get_deadline(&env), None);
+        });
+        assert_eq!(client.try_deposit(&account, &1), Err(Ok(VaultError::NotOpened)));
+    }
+
+    #[test]
+    fn test_macros_state_machine_events() {
+        use soroban_sdk::{symbol_short, testutils::Events, vec, IntoVal, Val};
+
+        let env = Env::default();
+        let contract_id = env.register_contract(None, TestContract);
+        TestContractClient::new(&env, &contract_id).test_transition_events();
+
+        // Topics ("fsm", "state", region), data (previous state, new state).
+        let topics: soroban_sdk::Vec<Val> =
+            (symbol_short!("fsm"), symbol_short!("state"), Room::Public).into_val(&env);
+        let running: (Option<Job>, Option<Job>) = (Some(Job::Idle), Some(Job::Running(1)));
+        let removed: (Option<Job>, Option<Job>) = (Some(Job::Running(1)), None);
+        assert_eq!(
+            env.events().all(),
+            vec![
+                &env,
+                (contract_id.clone(), topics.clone(), running.into_val(&env)),
+                (contract_id.clone(), topics, removed.into_val(&env)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_macros_fallible_state_machine() {
+        let env = Env::default();
+        TestContractClient::new(&env, &env.register_contract(None, TestContract))
+            .test_fallible_state_machine();
+    }
+
+    #[test]
+    fn test_macros_state_machine_transitions() {
+        let env = Env::default();
+        TestContractClient::new(&env, &env.register_contract(None, TestContract))
+            .test_transitions();
+    }
+
     #[test]
     fn test_macros_state_machine() {
         let env = Env::default();
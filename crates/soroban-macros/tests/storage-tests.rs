@@ -19,11 +19,14 @@ mod tests {
 
     use core::panic::AssertUnwindSafe;
     use soroban_sdk::{
-        contract, contractimpl, contracttype, testutils::Address as _, Address, Env, IntoVal,
-        TryFromVal, Val,
+        contract, contractimpl, contracttype,
+        testutils::Address as _,
+        Address, Bytes, BytesN, ConversionError, Env, IntoVal, Symbol, TryFromVal, Val,
     };
+    #[cfg(not(feature = "mock-storage"))]
+    use soroban_sdk::testutils::Ledger;
 
-    use soroban_macros::{key_constraint, storage};
+    use soroban_macros::{key_constraint, storage, StorageKey};
     use soroban_tools::storage;
 
     use std::panic::catch_unwind;
@@ -115,6 +118,306 @@ mod tests {
         pub address: Address,
     }
 
+    // Contract type for quote data.
+    #[contracttype]
+    // Implement the temporary storage for quote data, extending the
+    // entry TTL whenever it is read or written.
+    #[storage(Temporary, ttl(threshold = 50, extend_to = 100), extend_on = "read|write")]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct QuoteData {
+        pub price: i128,
+    }
+
+    // Schema versions of the profile data. Entries written with an older
+    // version are migrated on read.
+    #[contracttype]
+    #[storage(Persistent)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct ProfileV1 {
+        pub name: Symbol,
+    }
+
+    #[contracttype]
+    #[storage(Persistent, version = 2, migrate_from = ProfileV1)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct ProfileV2 {
+        pub name: Symbol,
+        pub level: u32,
+    }
+
+    impl From<ProfileV1> for ProfileV2 {
+        fn from(data: ProfileV1) -> Self {
+            ProfileV2 {
+                name: data.name,
+                level: 1,
+            }
+        }
+    }
+
+    #[contracttype]
+    #[storage(Persistent, version = 3, migrate_from = ProfileV2)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct ProfileV3 {
+        pub name: Symbol,
+        pub level: u32,
+        pub score: i128,
+    }
+
+    impl From<ProfileV2> for ProfileV3 {
+        fn from(data: ProfileV2) -> Self {
+            ProfileV3 {
+                name: data.name,
+                level: data.level,
+                score: data.level as i128 * 10,
+            }
+        }
+    }
+
+    // Contract type for order data. The OrderDataKey key type is derived
+    // from the #[key] fields and constrains the storage.
+    #[contracttype]
+    #[storage(Persistent)]
+    #[derive(Clone, Debug, Eq, PartialEq, StorageKey)]
+    pub struct OrderData {
+        #[key]
+        pub owner: Address,
+        #[key]
+        pub id: u64,
+        pub amount: i128,
+    }
+
+    // Contract type for message data, indexed by publisher.
+    #[contracttype]
+    #[storage(Persistent, index(publisher))]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct MessageData {
+        pub publisher: Address,
+        pub text: Symbol,
+    }
+
+    // Contract type for audited data, publishing storage events.
+    #[contracttype]
+    #[storage(Persistent, events)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct AuditData {
+        pub value: u32,
+    }
+
+    // Contract type for offer data, valid for 100 ledgers after it is written.
+    #[contracttype]
+    #[storage(Temporary, expires_after = 100)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct OfferData {
+        pub price: i128,
+    }
+
+    // Versioned contract type for ask data, expiring after 100 ledgers.
+    #[contracttype]
+    #[storage(Temporary, expires_after = 100, version = 2)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct AskData {
+        pub price: i128,
+    }
+
+    // Tuple contract type for range data, expiring after 100 ledgers.
+    #[contracttype]
+    #[storage(Temporary, expires_after = 100)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct RangeData(pub u32, pub u32);
+
+    // Tuple and enum contract types for pair and mode data, versioned
+    // once entries were written.
+    #[contracttype]
+    #[storage(Persistent)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct PairV1(pub u32, pub i128);
+
+    #[contracttype]
+    #[storage(Persistent, version = 2, migrate_from = PairV1)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct PairV2 {
+        pub id: u32,
+        pub amount: i128,
+    }
+
+    impl From<PairV1> for PairV2 {
+        fn from(data: PairV1) -> Self {
+            PairV2 {
+                id: data.0,
+                amount: data.1,
+            }
+        }
+    }
+
+    #[contracttype]
+    #[storage(Persistent)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub enum ModeV1 {
+        Off,
+        Range(u32, u32),
+    }
+
+    #[contracttype]
+    #[storage(Persistent, version = 2, migrate_from = ModeV1)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub enum ModeV2 {
+        Off,
+        Range(u32, u32),
+        Max,
+    }
+
+    impl From<ModeV1> for ModeV2 {
+        fn from(data: ModeV1) -> Self {
+            match data {
+                ModeV1::Off => ModeV2::Off,
+                ModeV1::Range(from, to) => ModeV2::Range(from, to),
+            }
+        }
+    }
+
+    // Contract type for bid data, removed once read after its expiry.
+    #[contracttype]
+    #[storage(Persistent, expires_after = 10, remove_expired)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct BidData {
+        pub amount: i128,
+    }
+
+    // Item keys holding data are recorded in a registry.
+    #[contracttype]
+    #[key_constraint(ItemKeyConstraint, registry)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub enum ItemKey {
+        Item(u32),
+    }
+
+    #[contracttype]
+    #[storage(Persistent, ItemKeyConstraint, ttl(threshold = 5000, extend_to = 10000))]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct ItemData {
+        pub quantity: u32,
+    }
+
+    #[contracttype]
+    #[storage(Temporary, ItemKeyConstraint)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct ItemLock {
+        pub owner: Address,
+    }
+
+    // Topic keys embed unbounded topics and are stored hashed.
+    #[contracttype]
+    #[key_constraint(TopicKeyConstraint, hashed, registry)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub enum TopicKey {
+        Topic(Bytes),
+    }
+
+    #[contracttype]
+    #[storage(Persistent, TopicKeyConstraint)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct TopicData {
+        pub count: u32,
+    }
+
+    #[contracttype]
+    #[storage(Temporary, TopicKeyConstraint, ttl(threshold = 50, extend_to = 100))]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct TopicDraft {
+        pub text: Symbol,
+    }
+
+    // Trade data stored with the packed encoding (bool fields as bit flags
+    // followed by the fixed-width fields).
+    #[storage(Persistent, packed)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct TradeData {
+        pub price: i128,
+        pub active: bool,
+        pub quantity: u32,
+        pub settled: bool,
+        pub timestamp: u64,
+    }
+
+    // Same layout with the #[contracttype] encoding.
+    #[contracttype]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct TradeRecord {
+        pub price: i128,
+        pub active: bool,
+        pub quantity: u32,
+        pub settled: bool,
+        pub timestamp: u64,
+    }
+
+    #[storage(Temporary, packed)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct FlagsData(
+        pub bool,
+        pub bool,
+        pub bool,
+        pub bool,
+        pub bool,
+        pub bool,
+        pub bool,
+        pub bool,
+        pub bool,
+        pub u8,
+    );
+
+    // Contract type for phase data, stored as an enum.
+    #[contracttype]
+    #[storage(Persistent)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub enum PhaseData {
+        Open,
+        Closed(u64),
+    }
+
+    // Generic key and data types. Type parameters and where-clauses are
+    // propagated to the key constraint and storage implementations.
+    #[key_constraint(SlotKeyConstraint)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct SlotKey<T>
+    where
+        T: Clone,
+    {
+        pub id: T,
+    }
+
+    #[storage(Persistent, SlotKeyConstraint)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct Position<T> {
+        pub value: T,
+    }
+
+    // Generic types are not supported by #[contracttype], convert through
+    // the wrapped value instead.
+    macro_rules! impl_wrapper_val {
+        ($wrapper:ident, $field:ident $(, $bound:path)?) => {
+            impl<T: IntoVal<Env, Val> $(+ $bound)?> TryFromVal<Env, $wrapper<T>> for Val {
+                type Error = ConversionError;
+
+                fn try_from_val(env: &Env, data: &$wrapper<T>) -> Result<Val, ConversionError> {
+                    Ok(data.$field.into_val(env))
+                }
+            }
+
+            impl<T: TryFromVal<Env, Val> $(+ $bound)?> TryFromVal<Env, Val> for $wrapper<T> {
+                type Error = ConversionError;
+
+                fn try_from_val(env: &Env, val: &Val) -> Result<Self, ConversionError> {
+                    Ok($wrapper {
+                        $field: T::try_from_val(env, val).map_err(|_| ConversionError)?,
+                    })
+                }
+            }
+        };
+    }
+
+    impl_wrapper_val!(SlotKey, id, Clone);
+    impl_wrapper_val!(Position, value);
+
     #[contract]
     pub struct TestContract;
 
@@ -162,6 +465,214 @@ mod tests {
                 },
             );
         }
+
+        pub fn test_packed_storage(env: Env) {
+            run_storage_tests(
+                &env,
+                &UserKey::Session(1),
+                TradeData {
+                    price: -100,
+                    active: true,
+                    quantity: 10,
+                    settled: false,
+                    timestamp: u64::MAX,
+                },
+            );
+            run_storage_tests(
+                &env,
+                &UserKey::Session(2),
+                FlagsData(true, false, false, true, false, false, false, true, true, 7),
+            );
+        }
+
+        pub fn test_enum_storage(env: Env) {
+            run_storage_tests(&env, &UserKey::Session(1), PhaseData::Closed(100));
+        }
+
+        pub fn test_generic_storage(env: Env) {
+            run_storage_tests(&env, &SlotKey { id: 1u64 }, Position { value: 100i128 });
+            run_storage_tests(
+                &env,
+                &SlotKey {
+                    id: Symbol::new(&env, "alice"),
+                },
+                Position {
+                    value: Address::generate(&env),
+                },
+            );
+        }
+
+        pub fn set_quote(env: Env, id: u64, price: i128) {
+            storage::set(&env, &UserKey::Session(id), &QuoteData { price });
+        }
+
+        pub fn get_quote(env: Env, id: u64) -> i128 {
+            storage::get::<_, QuoteData>(&env, &UserKey::Session(id))
+                .unwrap()
+                .price
+        }
+
+        pub fn has_quote(env: Env, id: u64) -> bool {
+            storage::has::<_, QuoteData>(&env, &UserKey::Session(id))
+        }
+
+        pub fn set_profile_v1(env: Env, id: u64, name: Symbol) {
+            storage::set(&env, &UserKey::Session(id), &ProfileV1 { name });
+        }
+
+        pub fn set_profile_v2(env: Env, id: u64, name: Symbol, level: u32) {
+            storage::set(&env, &UserKey::Session(id), &ProfileV2 { name, level });
+        }
+
+        pub fn place_order(env: Env, owner: Address, id: u64, amount: i128) {
+            let order = OrderData { owner, id, amount };
+            storage::set(&env, &order.storage_key(), &order);
+        }
+
+        pub fn get_order(env: Env, owner: Address, id: u64) -> Option<OrderData> {
+            storage::get(&env, &OrderDataKey::OrderData(owner, id))
+        }
+
+        pub fn post_message(env: Env, id: u64, publisher: Address, text: Symbol) {
+            storage::set(&env, &UserKey::Session(id), &MessageData { publisher, text });
+        }
+
+        pub fn delete_message(env: Env, id: u64) {
+            storage::remove::<_, MessageData>(&env, &UserKey::Session(id));
+        }
+
+        pub fn find_messages(env: Env, publisher: Address) -> soroban_sdk::Vec<UserKey> {
+            MessageData::find_by_publisher(&env, &publisher)
+        }
+
+        pub fn demote_message(env: Env, id: u64) {
+            storage::demote::<_, MessageData>(&env, &UserKey::Session(id), storage::MoveTtl::Reset)
+                .unwrap();
+        }
+
+        pub fn promote_message(env: Env, id: u64) {
+            storage::promote::<_, MessageData>(&env, &UserKey::Session(id), storage::MoveTtl::Reset)
+                .unwrap();
+        }
+
+        pub fn set_audit(env: Env, id: u64, value: u32) {
+            storage::set(&env, &UserKey::Session(id), &AuditData { value });
+        }
+
+        pub fn extend_audit(env: Env, id: u64) {
+            storage::extend_ttl::<_, AuditData>(&env, &UserKey::Session(id), 100, 200);
+        }
+
+        pub fn remove_audit(env: Env, id: u64) {
+            storage::remove::<_, AuditData>(&env, &UserKey::Session(id));
+        }
+
+        pub fn set_offer(env: Env, id: u64, price: i128) {
+            storage::set(&env, &UserKey::Session(id), &OfferData { price });
+        }
+
+        pub fn get_offer(env: Env, id: u64) -> Option<OfferData> {
+            storage::get(&env, &UserKey::Session(id))
+        }
+
+        pub fn has_offer(env: Env, id: u64) -> bool {
+            storage::has::<_, OfferData>(&env, &UserKey::Session(id))
+        }
+
+        pub fn set_bid(env: Env, id: u64, amount: i128) {
+            storage::set(&env, &UserKey::Session(id), &BidData { amount });
+        }
+
+        pub fn get_bid(env: Env, id: u64) -> Option<BidData> {
+            storage::get(&env, &UserKey::Session(id))
+        }
+
+        pub fn has_entry(env: Env, id: u64) -> bool {
+            env.storage().persistent().has(&UserKey::Session(id))
+        }
+
+        pub fn add_item(env: Env, id: u32, quantity: u32) {
+            storage::set(&env, &ItemKey::Item(id), &ItemData { quantity });
+        }
+
+        pub fn remove_item(env: Env, id: u32) {
+            storage::remove::<_, ItemData>(&env, &ItemKey::Item(id));
+        }
+
+        pub fn lock_item(env: Env, id: u32, owner: Address) {
+            storage::set(&env, &ItemKey::Item(id), &ItemLock { owner });
+        }
+
+        pub fn unlock_item(env: Env, id: u32) {
+            storage::remove::<_, ItemLock>(&env, &ItemKey::Item(id));
+        }
+
+        pub fn list_items(env: Env, cursor: u32, limit: u32) -> soroban_sdk::Vec<ItemKey> {
+            storage::keys(&env, cursor, limit)
+        }
+
+        pub fn demote_item(env: Env, id: u32) {
+            storage::demote::<_, ItemData>(&env, &ItemKey::Item(id), storage::MoveTtl::CarryOver)
+                .unwrap();
+        }
+
+        pub fn remove_demoted_item(env: Env, id: u32) {
+            storage::temporary::remove::<_, ItemData>(&env, &ItemKey::Item(id));
+        }
+
+        pub fn extend_items(env: Env, cursor: u32, limit: u32) {
+            storage::extend_registry_ttl::<ItemKey>(&env, cursor, limit, 5000, 10000);
+        }
+
+        pub fn post_topic(env: Env, topic: Bytes) -> BytesN<32> {
+            let key = storage::HashedKey::with_preimage(&env, &TopicKey::Topic(topic));
+            let count = storage::get::<_, TopicData>(&env, &key).map_or(0, |data| data.count);
+            storage::set(&env, &key, &TopicData { count: count + 1 });
+            key.hash().clone()
+        }
+
+        pub fn delete_topic(env: Env, topic: Bytes) {
+            let key = storage::HashedKey::new(&env, &TopicKey::Topic(topic));
+            storage::remove::<_, TopicData>(&env, &key);
+        }
+
+        pub fn draft_topic(env: Env, topic: Bytes, text: Symbol) {
+            let key = storage::HashedKey::with_preimage(&env, &TopicKey::Topic(topic));
+            storage::set(&env, &key, &TopicDraft { text });
+        }
+
+        pub fn delete_draft(env: Env, topic: Bytes) {
+            let key = storage::HashedKey::new(&env, &TopicKey::Topic(topic));
+            storage::remove::<_, TopicDraft>(&env, &key);
+        }
+
+        pub fn get_topic_count(env: Env, topic: Bytes) -> Option<u32> {
+            let key = storage::HashedKey::new(&env, &TopicKey::Topic(topic));
+            storage::get::<_, TopicData>(&env, &key).map(|data| data.count)
+        }
+
+        pub fn list_topics(env: Env, cursor: u32, limit: u32) -> soroban_sdk::Vec<Bytes> {
+            let mut topics = soroban_sdk::Vec::new(&env);
+            for key in storage::keys::<storage::HashedKey<TopicKey>>(&env, cursor, limit) {
+                if let Some(TopicKey::Topic(topic)) = key.preimage(&env) {
+                    topics.push_back(topic);
+                }
+            }
+            topics
+        }
+
+        pub fn get_profile(env: Env, id: u64) -> ProfileV3 {
+            storage::get(&env, &UserKey::Session(id)).unwrap()
+        }
+
+        pub fn get_profile_tag(env: Env, id: u64) -> u32 {
+            let (_, version, _): (Symbol, u32, Val) = env
+                .storage()
+                .persistent()
+                .get(&UserKey::Session(id))
+                .unwrap();
+            version
+        }
     }
 
     #[cfg(not(feature = "mock-storage"))]
@@ -188,6 +699,533 @@ mod tests {
             .test_temporary_storage();
     }
 
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_macros_enum_storage() {
+        let env = Env::default();
+        TestContractClient::new(&env, &env.register_contract(None, TestContract))
+            .test_enum_storage();
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_macros_generic_storage() {
+        let env = Env::default();
+        TestContractClient::new(&env, &env.register_contract(None, TestContract))
+            .test_generic_storage();
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_macros_packed_storage() {
+        let env = Env::default();
+        TestContractClient::new(&env, &env.register_contract(None, TestContract))
+            .test_packed_storage();
+    }
+
+    #[test]
+    fn test_macros_packed_round_trip() {
+        use soroban_sdk::{xdr::ToXdr, Bytes};
+        use soroban_tools::storage::Packed;
+
+        let env = Env::default();
+        let trades = [
+            (0, false, 0, false, 0),
+            (i128::MIN, true, u32::MAX, false, 1),
+            (i128::MAX, false, 1, true, u64::MAX),
+            (-1, true, 42, true, 1225476633),
+        ];
+        for (price, active, quantity, settled, timestamp) in trades {
+            let data = TradeData {
+                price,
+                active,
+                quantity,
+                settled,
+                timestamp,
+            };
+            let bytes = data.pack(&env);
+            assert_eq!(bytes.len(), TradeData::SIZE);
+            assert_eq!(TradeData::unpack(&bytes), Some(data.clone()));
+            let val: Val = data.clone().into_val(&env);
+            assert_eq!(TradeData::try_from_val(&env, &val), Ok(data.clone()));
+
+            // Packed data is smaller than the #[contracttype] encoding.
+            let record = TradeRecord {
+                price,
+                active,
+                quantity,
+                settled,
+                timestamp,
+            };
+            assert!(val.to_xdr(&env).len() * 2 < record.to_xdr(&env).len());
+        }
+
+        // 1 flag byte, 16 + 4 + 8 bytes of fixed-width fields.
+        assert_eq!(TradeData::SIZE, 29);
+
+        // Flags are spread over as many bytes as needed.
+        assert_eq!(FlagsData::SIZE, 3);
+        for bits in [0u32, 1, 0b1_0000_0000, 0b1_1010_0101, 0b1_1111_1111] {
+            let flag = |index: u32| bits & (1 << index) != 0;
+            let data = FlagsData(
+                flag(0),
+                flag(1),
+                flag(2),
+                flag(3),
+                flag(4),
+                flag(5),
+                flag(6),
+                flag(7),
+                flag(8),
+                bits as u8,
+            );
+            assert_eq!(FlagsData::unpack(&data.pack(&env)), Some(data));
+        }
+
+        // Mismatching layouts are rejected.
+        assert_eq!(TradeData::unpack(&Bytes::from_array(&env, &[0; 28])), None);
+        assert!(FlagsData::try_from_val(&env, &Val::from(1u32)).is_err());
+    }
+
+    // Live-until ledger of the (single) temporary entry.
+    #[cfg(not(feature = "mock-storage"))]
+    fn temporary_live_until(env: &Env) -> Option<u32> {
+        use soroban_sdk::xdr::{ContractDataDurability, LedgerKey};
+        env.to_ledger_snapshot()
+            .entries()
+            .into_iter()
+            .find_map(|(key, (_, live_until))| match key.as_ref() {
+                LedgerKey::ContractData(data)
+                    if data.durability == ContractDataDurability::Temporary =>
+                {
+                    *live_until
+                }
+                _ => None,
+            })
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_macros_storage_ttl_policy() {
+        let env = Env::default();
+        let client = TestContractClient::new(&env, &env.register_contract(None, TestContract));
+
+        // Writing extends the entry TTL beyond the minimum temporary TTL.
+        client.set_quote(&1, &100);
+        assert_eq!(temporary_live_until(&env), Some(100));
+
+        // Reading does not extend the TTL above the threshold.
+        env.ledger().with_mut(|li| li.sequence_number += 40);
+        assert_eq!(client.get_quote(&1), 100);
+        assert_eq!(temporary_live_until(&env), Some(100));
+
+        // Reading extends the TTL below the threshold.
+        env.ledger().with_mut(|li| li.sequence_number += 20);
+        assert_eq!(client.get_quote(&1), 100);
+        assert_eq!(temporary_live_until(&env), Some(160));
+
+        // Checking existence is not an access.
+        env.ledger().with_mut(|li| li.sequence_number += 60);
+        assert!(client.has_quote(&1));
+        assert_eq!(temporary_live_until(&env), Some(160));
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_macros_storage_versioning() {
+        let env = Env::default();
+        let client = TestContractClient::new(&env, &env.register_contract(None, TestContract));
+        let name = Symbol::new(&env, "alice");
+
+        // Untagged data is migrated through every version.
+        client.set_profile_v1(&1, &name);
+        let profile = ProfileV3 {
+            name: name.clone(),
+            level: 1,
+            score: 10,
+        };
+        assert_eq!(client.get_profile(&1), profile);
+
+        // And written back with the current version tag.
+        assert_eq!(client.get_profile_tag(&1), 3);
+        assert_eq!(client.get_profile(&1), profile);
+
+        // Tagged data is migrated from its version.
+        client.set_profile_v2(&2, &name, &5);
+        assert_eq!(client.get_profile_tag(&2), 2);
+        assert_eq!(client.get_profile(&2).score, 50);
+        assert_eq!(client.get_profile_tag(&2), 3);
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_macros_storage_versioning_encoding() {
+        let env = Env::default();
+        let contract_id = env.register_contract(None, TestContract);
+        env.as_contract(&contract_id, || {
+            let keys = [UserKey::Session(1), UserKey::Session(2), UserKey::Session(3)];
+
+            // Untagged tuples and enums are not mistaken for versioned data.
+            storage::set(&env, &keys[0], &PairV1(3, 100));
+            storage::set(&env, &keys[1], &ModeV1::Off);
+            storage::set(&env, &keys[2], &ModeV1::Range(1, 2));
+            let pair = PairV2 { id: 3, amount: 100 };
+            assert_eq!(storage::get(&env, &keys[0]), Some(pair.clone()));
+            assert_eq!(storage::get(&env, &keys[1]), Some(ModeV2::Off));
+            assert_eq!(storage::get(&env, &keys[2]), Some(ModeV2::Range(1, 2)));
+
+            // Migrated data is written back with the version tag.
+            let (tag, version, _): (Symbol, u32, Val) =
+                env.storage().persistent().get(&keys[0]).unwrap();
+            assert_eq!((tag, version), (Symbol::new(&env, "v"), 2));
+            assert_eq!(storage::get(&env, &keys[0]), Some(pair));
+        });
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_macros_storage_key_derive() {
+        let env = Env::default();
+        let client = TestContractClient::new(&env, &env.register_contract(None, TestContract));
+        let (alice, bob) = (Address::generate(&env), Address::generate(&env));
+
+        client.place_order(&alice, &1, &100);
+        client.place_order(&alice, &2, &200);
+        client.place_order(&bob, &1, &300);
+
+        // Orders are keyed by owner and id.
+        assert_eq!(client.get_order(&alice, &2).unwrap().amount, 200);
+        assert_eq!(client.get_order(&bob, &1).unwrap().amount, 300);
+        assert_eq!(client.get_order(&bob, &2), None);
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_macros_storage_index() {
+        use soroban_sdk::vec;
+
+        let env = Env::default();
+        let client = TestContractClient::new(&env, &env.register_contract(None, TestContract));
+        let (alice, bob) = (Address::generate(&env), Address::generate(&env));
+        let text = Symbol::new(&env, "hello");
+
+        client.post_message(&1, &alice, &text);
+        client.post_message(&2, &alice, &text);
+        client.post_message(&3, &bob, &text);
+        assert_eq!(
+            client.find_messages(&alice),
+            vec![&env, UserKey::Session(1), UserKey::Session(2)]
+        );
+        assert_eq!(client.find_messages(&bob), vec![&env, UserKey::Session(3)]);
+
+        // Overwriting moves the key to the new publisher index.
+        client.post_message(&2, &bob, &text);
+        client.post_message(&1, &alice, &Symbol::new(&env, "edited"));
+        assert_eq!(client.find_messages(&alice), vec![&env, UserKey::Session(1)]);
+        assert_eq!(
+            client.find_messages(&bob),
+            vec![&env, UserKey::Session(3), UserKey::Session(2)]
+        );
+
+        // Removing deletes the key from the index.
+        client.delete_message(&3);
+        client.delete_message(&1);
+        assert_eq!(client.find_messages(&alice), vec![&env]);
+        assert_eq!(client.find_messages(&bob), vec![&env, UserKey::Session(2)]);
+
+        // Index entries move along with the data.
+        client.demote_message(&2);
+        assert_eq!(client.find_messages(&bob), vec![&env]);
+        client.promote_message(&2);
+        assert_eq!(client.find_messages(&bob), vec![&env, UserKey::Session(2)]);
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_macros_storage_expiry() {
+        let env = Env::default();
+        let client = TestContractClient::new(&env, &env.register_contract(None, TestContract));
+
+        // Offers are readable up to their expiry ledger.
+        client.set_offer(&1, &100);
+        env.ledger().with_mut(|li| li.sequence_number += 100);
+        assert_eq!(client.get_offer(&1), Some(OfferData { price: 100 }));
+        assert!(client.has_offer(&1));
+        env.ledger().with_mut(|li| li.sequence_number += 1);
+        assert_eq!(client.get_offer(&1), None);
+        assert!(!client.has_offer(&1));
+
+        // Rewriting resets the expiry ledger.
+        client.set_offer(&1, &200);
+        assert_eq!(client.get_offer(&1), Some(OfferData { price: 200 }));
+
+        // Expired bids are removed when read.
+        client.set_bid(&2, &50);
+        env.ledger().with_mut(|li| li.sequence_number += 11);
+        assert!(client.has_entry(&2));
+        assert_eq!(client.get_bid(&2), None);
+        assert!(!client.has_entry(&2));
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_macros_storage_expiry_encoding() {
+        let env = Env::default();
+        let contract_id = env.register_contract(None, TestContract);
+        env.as_contract(&contract_id, || {
+            let (ask_key, range_key) = (UserKey::Session(1), UserKey::Session(2));
+            let ask = AskData { price: 10 };
+            let range = RangeData(5, 7);
+
+            // Versioned and tuple data written before expiry was enabled
+            // is not mistaken for expiring data.
+            let versioned = storage::to_versioned_val(&env, &ask);
+            env.storage().temporary().set(&ask_key, &versioned);
+            env.storage().temporary().set(&range_key, &range);
+            assert_eq!(storage::get(&env, &ask_key), Some(ask.clone()));
+            assert_eq!(storage::get(&env, &range_key), Some(range.clone()));
+
+            // Expiring data reads back until its expiry ledger.
+            storage::set(&env, &ask_key, &ask);
+            storage::set(&env, &range_key, &range);
+            env.ledger().with_mut(|li| li.sequence_number += 100);
+            assert_eq!(storage::get(&env, &ask_key), Some(ask));
+            assert_eq!(storage::get(&env, &range_key), Some(range));
+            env.ledger().with_mut(|li| li.sequence_number += 1);
+            assert!(!storage::has::<_, AskData>(&env, &ask_key));
+            assert_eq!(storage::get::<_, RangeData>(&env, &range_key), None);
+        });
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_macros_key_registry() {
+        use soroban_sdk::vec;
+
+        let env = Env::default();
+        let client = TestContractClient::new(&env, &env.register_contract(None, TestContract));
+
+        for id in 1..=4 {
+            client.add_item(&id, &10);
+        }
+        client.add_item(&2, &20);
+        assert_eq!(
+            client.list_items(&0, &3),
+            vec![&env, ItemKey::Item(1), ItemKey::Item(2), ItemKey::Item(3)]
+        );
+        assert_eq!(client.list_items(&3, &3), vec![&env, ItemKey::Item(4)]);
+
+        // Keys remain registered while any tier holds data.
+        client.lock_item(&1, &Address::generate(&env));
+        client.remove_item(&1);
+        client.remove_item(&2);
+        assert_eq!(
+            client.list_items(&0, &10),
+            vec![&env, ItemKey::Item(1), ItemKey::Item(4), ItemKey::Item(3)]
+        );
+        client.unlock_item(&1);
+        assert_eq!(
+            client.list_items(&0, &10),
+            vec![&env, ItemKey::Item(3), ItemKey::Item(4)]
+        );
+
+        // Keys moved between tiers remain registered.
+        client.demote_item(&3);
+        assert_eq!(
+            client.list_items(&0, &10),
+            vec![&env, ItemKey::Item(3), ItemKey::Item(4)]
+        );
+        client.remove_demoted_item(&3);
+        assert_eq!(client.list_items(&0, &10), vec![&env, ItemKey::Item(4)]);
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    fn persistent_live_until<K: IntoVal<Env, Val>>(env: &Env, key: &K) -> Option<u32> {
+        use soroban_sdk::xdr::{ContractDataDurability, LedgerKey, ScVal};
+        let key = ScVal::try_from_val(env, &key.into_val(env)).unwrap();
+        env.to_ledger_snapshot()
+            .entries()
+            .into_iter()
+            .find_map(|(ledger_key, (_, live_until))| match ledger_key.as_ref() {
+                LedgerKey::ContractData(data)
+                    if data.durability == ContractDataDurability::Persistent
+                        && data.key == key =>
+                {
+                    *live_until
+                }
+                _ => None,
+            })
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_macros_key_registry_ttl() {
+        let env = Env::default();
+        let client = TestContractClient::new(&env, &env.register_contract(None, TestContract));
+        let registry_key = (
+            (
+                soroban_sdk::symbol_short!("registry"),
+                soroban_sdk::String::from_str(&env, "ItemKey"),
+            ),
+            ItemKey::Item(1),
+        );
+
+        // Registry entries follow the TTL policy of the data.
+        client.add_item(&1, &10);
+        let live_until = env.ledger().sequence() + 10000;
+        assert_eq!(persistent_live_until(&env, &ItemKey::Item(1)), Some(live_until));
+        assert_eq!(persistent_live_until(&env, &registry_key), Some(live_until));
+
+        // Registry entries are extended independently of the data.
+        env.ledger().with_mut(|li| li.sequence_number += 6000);
+        client.extend_items(&0, &10);
+        assert_eq!(persistent_live_until(&env, &ItemKey::Item(1)), Some(live_until));
+        assert_eq!(persistent_live_until(&env, &registry_key), Some(live_until + 6000));
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_macros_hashed_keys() {
+        use soroban_sdk::{vec, xdr::ToXdr};
+
+        let env = Env::default();
+        let contract_id = env.register_contract(None, TestContract);
+        let client = TestContractClient::new(&env, &contract_id);
+
+        // Ledger keys are the hash of the XDR-encoded key, whatever the topic size.
+        let long_topic = Bytes::from_array(&env, &[7; 1024]);
+        let hash = client.post_topic(&long_topic);
+        let expected = TopicKey::Topic(long_topic.clone()).to_xdr(&env);
+        assert_eq!(hash, env.crypto().sha256(&expected));
+        env.as_contract(&contract_id, || {
+            assert!(env.storage().persistent().has(&hash));
+            assert!(!env
+                .storage()
+                .persistent()
+                .has(&TopicKey::Topic(long_topic.clone())));
+        });
+
+        let short_topic = Bytes::from_array(&env, &[1, 2, 3]);
+        client.post_topic(&short_topic);
+        client.post_topic(&long_topic);
+        assert_eq!(client.get_topic_count(&long_topic), Some(2));
+        assert_eq!(client.get_topic_count(&short_topic), Some(1));
+        assert_eq!(client.get_topic_count(&Bytes::new(&env)), None);
+
+        // Registered keys are enumerated through their stored preimage.
+        assert_eq!(
+            client.list_topics(&0, &10),
+            vec![&env, long_topic.clone(), short_topic.clone()]
+        );
+
+        // Preimages are stored in the data tier and removed along with the data.
+        let draft_topic = Bytes::from_array(&env, &[4, 5, 6]);
+        client.draft_topic(&draft_topic, &Symbol::new(&env, "draft"));
+        let draft_hash = env.crypto().sha256(&TopicKey::Topic(draft_topic.clone()).to_xdr(&env));
+        let preimage_key = (soroban_sdk::symbol_short!("preimage"), draft_hash);
+        env.as_contract(&contract_id, || {
+            assert!(env.storage().temporary().has(&preimage_key));
+            assert!(!env.storage().persistent().has(&preimage_key));
+        });
+        let temporary_ttls: std::vec::Vec<_> = env
+            .to_ledger_snapshot()
+            .entries()
+            .into_iter()
+            .filter_map(|(key, (_, live_until))| match key.as_ref() {
+                soroban_sdk::xdr::LedgerKey::ContractData(data)
+                    if data.durability == soroban_sdk::xdr::ContractDataDurability::Temporary =>
+                {
+                    *live_until
+                }
+                _ => None,
+            })
+            .collect();
+        let live_until = env.ledger().sequence() + 100;
+        assert_eq!(temporary_ttls, std::vec![live_until, live_until]);
+        assert_eq!(
+            client.list_topics(&0, &10),
+            vec![&env, long_topic.clone(), short_topic.clone(), draft_topic.clone()]
+        );
+        client.delete_draft(&draft_topic);
+        env.as_contract(&contract_id, || {
+            assert!(!env.storage().temporary().has(&preimage_key));
+        });
+        client.delete_topic(&short_topic);
+        assert_eq!(client.list_topics(&0, &10), vec![&env, long_topic]);
+        env.as_contract(&contract_id, || {
+            let short_hash = env.crypto().sha256(&TopicKey::Topic(short_topic).to_xdr(&env));
+            assert!(!env
+                .storage()
+                .persistent()
+                .has(&(soroban_sdk::symbol_short!("preimage"), short_hash)));
+        });
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_macros_storage_events() {
+        use soroban_sdk::{testutils::Events, vec, String};
+
+        let env = Env::default();
+        let contract_id = env.register_contract(None, TestContract);
+        let client = TestContractClient::new(&env, &contract_id);
+        let key = UserKey::Session(1);
+        let event = |operation: &str| {
+            (
+                contract_id.clone(),
+                (
+                    Symbol::new(&env, "storage"),
+                    Symbol::new(&env, operation),
+                    String::from_str(&env, "AuditData"),
+                )
+                    .into_val(&env),
+                key.into_val(&env),
+            )
+        };
+
+        client.set_audit(&1, &10);
+        client.extend_audit(&1);
+        client.remove_audit(&1);
+
+        // Data types without the events option are silent.
+        client.set_quote(&1, &100);
+        assert_eq!(
+            env.events().all(),
+            vec![&env, event("set"), event("extend_ttl"), event("remove")]
+        );
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_macros_storage_map_events() {
+        use soroban_sdk::{testutils::Events, String};
+
+        let env = Env::default();
+        let contract_id = env.register_contract(None, TestContract);
+        let key = UserKey::Session(2);
+
+        // Map values go through the typed storage of their data type.
+        env.as_contract(&contract_id, || {
+            let map = storage::StorageMap::<UserKey, u32, AuditData>::new(&key);
+            map.set(&env, &1, &AuditData { value: 10 });
+            assert!(map.remove(&env, &1));
+        });
+        let topics = |operation: &str| {
+            (
+                Symbol::new(&env, "storage"),
+                Symbol::new(&env, operation),
+                String::from_str(&env, "AuditData"),
+            )
+                .into_val(&env)
+        };
+        let events = env.events().all();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events.get(0).unwrap().1, topics("set"));
+        assert_eq!(events.get(1).unwrap().1, topics("remove"));
+        let data = <(UserKey, u32)>::try_from_val(&env, &events.get(1).unwrap().2);
+        assert_eq!(data, Ok((key, 1)));
+    }
+
     #[cfg(feature = "mock-storage")]
     #[test]
     fn test_macros_mock_storage() {
@@ -206,41 +1244,44 @@ mod tests {
         }
 
         let env = Env::default();
-        let key = &TestKey::Session(Address::generate(&env));
-        let data = TestData {
-            address: Address::generate(&env),
-        };
+        let contract_id = env.register_contract(None, TestContract);
+        env.as_contract(&contract_id, || {
+            let key = &TestKey::Session(Address::generate(&env));
+            let data = TestData {
+                address: Address::generate(&env),
+            };
 
-        // Try to get the data, should panic.
-        let result = catch_unwind(AssertUnwindSafe(|| {
-            assert_eq!(storage::get::<TestKey, TestData>(&env, &key).unwrap(), data);
-        }));
-        assert!(
-            result.is_err(),
-            "No data found. The operation should panic."
-        );
+            // Try to get the data, should panic.
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                assert_eq!(storage::get::<TestKey, TestData>(&env, &key).unwrap(), data);
+            }));
+            assert!(
+                result.is_err(),
+                "No data found. The operation should panic."
+            );
 
-        // Try to get the data with error tolerance, should not panic.
-        let result = catch_unwind(AssertUnwindSafe(|| {
-            storage::get_or_else::<TestKey, TestData, _, _>(&env, &key, |opt| {
-                opt.unwrap_or_else(|| data.clone())
-            });
-        }));
-        assert!(
-            !result.is_err(),
-            "No data found. The operation should not panic."
-        );
+            // Try to get the data with error tolerance, should not panic.
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                storage::get_or_else::<TestKey, TestData, _, _>(&env, &key, |opt| {
+                    opt.unwrap_or_else(|| data.clone())
+                });
+            }));
+            assert!(
+                !result.is_err(),
+                "No data found. The operation should not panic."
+            );
 
-        // Set the data.
-        storage::set::<TestKey, TestData>(&env, &key, &data);
+            // Set the data.
+            storage::set::<TestKey, TestData>(&env, &key, &data);
 
-        // Verify that the storage now has the data.
-        assert_eq!(storage::has::<TestKey, TestData>(&env, &key), true);
+            // Verify that the storage now has the data.
+            assert_eq!(storage::has::<TestKey, TestData>(&env, &key), true);
 
-        // Extend data TTL.
-        storage::extend_ttl::<TestKey, TestData>(&env, &key, 1, 1);
+            // Extend data TTL.
+            storage::extend_ttl::<TestKey, TestData>(&env, &key, 1, 1);
 
-        // Remove the data.
-        storage::remove::<TestKey, TestData>(&env, &key);
+            // Remove the data.
+            storage::remove::<TestKey, TestData>(&env, &key);
+        });
     }
 }
@@ -46,7 +46,11 @@ macro_rules! impl_circuit_breaker_state_machine {
                     sm.set_state($env, &false); // Default circuit state is closed (false).
                 }
                 $instance.on_guard($env, &sm);
-                assert_eq!(sm.get_state(&$env).unwrap(), $state_key);
+                match sm.try_get_state($env) {
+                    Ok(state) if state == $state_key => {}
+                    Ok(_) => soroban_sdk::panic_with_error!($env, $crate::error::KitError::StateMismatch),
+                    Err(error) => soroban_sdk::panic_with_error!($env, error),
+                }
                 $instance.on_effect($env, &sm);
             }
     };
@@ -0,0 +1,54 @@
+/*
+    Copyright (c) 2023-2024 Frederic Kyung-jin Rezeau (오경진 吳景振)
+
+    This file is part of soroban-kit.
+
+    Licensed under the MIT License, this software is provided "AS IS",
+    no liability assumed. For details, see the LICENSE file in the
+    root directory.
+
+    Author: Fred Kyung-jin Rezeau <fred@litemint.com>
+*/
+
+use soroban_sdk::contracterror;
+
+// Contract error codes shared by all soroban-kit modules.
+// Codes are grouped by module (1xx storage, 2xx state machine,
+// 3xx commitment scheme, 4xx oracle) and must remain stable
+// since clients decode them.
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum KitError {
+    // Storage: no data is stored for the key.
+    NotFound = 100,
+    // Storage: the index is out of the collection bounds.
+    OutOfBounds = 101,
+    // Storage: the stored data version cannot be migrated to the current schema.
+    InvalidVersion = 102,
+    // Storage: the counter would overflow.
+    Overflow = 103,
+    // Storage: the counter would underflow.
+    Underflow = 104,
+    // Storage: the write exceeds the storage quota of the account.
+    QuotaExceeded = 105,
+    // Storage: data is already stored for the key.
+    AlreadyExists = 106,
+
+    // State machine: no state is stored for the region.
+    StateNotFound = 200,
+    // State machine: the current state does not match the expected state.
+    StateMismatch = 201,
+    // State machine: the transition is not declared in the transition table.
+    InvalidTransition = 202,
+    // State machine: the deadline of the current state has passed (see check_timeout).
+    StateExpired = 203,
+
+    // Commitment scheme: the hash has already been committed.
+    AlreadyCommitted = 300,
+    // Commitment scheme: no commitment matches the revealed data.
+    CommitmentNotFound = 301,
+
+    // Oracle: the envelope is invalid for the current contract.
+    InvalidEnvelope = 400,
+}
@@ -0,0 +1,14 @@
+/*
+    Copyright (c) 2023-2024 Frederic Kyung-jin Rezeau (오경진 吳景振)
+
+    This file is part of soroban-kit.
+
+    Licensed under the MIT License, this software is provided "AS IS",
+    no liability assumed. For details, see the LICENSE file in the
+    root directory.
+
+    Author: Fred Kyung-jin Rezeau <fred@litemint.com>
+*/
+
+mod r#impl;
+pub use r#impl::*;
\ No newline at end of file
@@ -11,7 +11,12 @@
 */
 
 use core::marker::PhantomData;
-use soroban_sdk::{contracttype, Env, IntoVal, TryFromVal, Val};
+use soroban_sdk::{
+    contracttype, panic_with_error, symbol_short, ConversionError, Env, IntoVal, Symbol,
+    TryFromVal, Val, Vec,
+};
+
+use crate::error::KitError;
 
 // Control state transitions for the state machine.
 pub trait TransitionHandler<K, V>
@@ -28,6 +33,29 @@ where
     fn on_effect(&self, env: &Env, state_machine: &StateMachine<K, V>);
 }
 
+// Legal transitions between states (see transitions! macro).
+pub trait TransitionTable {
+    // Whether the transition table declares the `from -> to` edge.
+    // Edges are declared between variants, regardless of their tuple values.
+    fn is_transition_allowed(from: &Self, to: &Self) -> bool;
+}
+
+// Control state transitions for the state machine with fallible guards
+// (see #[state_machine(..., error = MyError)]).
+pub trait FallibleTransitionHandler<K, V, E>
+where
+    K: Clone + IntoVal<Env, Val> + TryFromVal<Env, Val>,
+    V: Clone + IntoVal<Env, Val> + TryFromVal<Env, Val>,
+{
+    // Called immediately before state validation.
+    // Returning an error aborts the transition with that error.
+    fn on_guard(&self, env: &Env, state_machine: &StateMachine<K, V>) -> Result<(), E>;
+
+    // Called immediately after state validation iff validation succeeded.
+    // Returning an error is returned from the transition.
+    fn on_effect(&self, env: &Env, state_machine: &StateMachine<K, V>) -> Result<(), E>;
+}
+
 // Generic finite state machine using Soroban storage for state serialization.
 // Support for state concurrency with regions and extended state variables to allow
 // modeling of complex behaviors.
@@ -38,6 +66,9 @@ where
 {
     region: &'a K,
     storage_type: StorageType,
+    events: bool,
+    history: u32,
+    function: Option<&'static str>,
     _data: PhantomData<*const V>,
 }
 
@@ -50,10 +81,42 @@ where
         StateMachine {
             region,
             storage_type,
+            events: false,
+            history: 0,
+            function: None,
             _data: PhantomData,
         }
     }
 
+    // Publish an event for every state change, with topics ("fsm", "state", region)
+    // and data (previous state, new state), None if no state is stored.
+    // Events and history only apply to changes made through this handle.
+    pub fn with_events(mut self, events: bool) -> Self {
+        self.events = events;
+        self
+    }
+
+    pub fn has_events(&self) -> bool {
+        self.events
+    }
+
+    // Record the last `capacity` state changes of the region (see `history`),
+    // 0 to disable. The capacity should not change once transitions are recorded.
+    pub fn with_history(mut self, capacity: u32) -> Self {
+        self.history = capacity;
+        self
+    }
+
+    pub fn get_history_capacity(&self) -> u32 {
+        self.history
+    }
+
+    // Name of the function recorded in the history (set by #[state_machine]).
+    pub fn with_function(mut self, function: &'static str) -> Self {
+        self.function = Some(function);
+        self
+    }
+
     pub fn get_region(&self) -> &'a K {
         self.region
     }
@@ -62,7 +125,19 @@ where
         &self.storage_type
     }
 
+    // Set the state, clearing the deadline of the previous state.
     pub fn set_state(&self, env: &Env, value: &V) {
+        self.store_deadline(env, None);
+        if self.events || self.history > 0 {
+            let previous = self.get_state(env);
+            self.store_state(env, value);
+            self.record_change(env, previous, Some(value.clone()));
+        } else {
+            self.store_state(env, value);
+        }
+    }
+
+    fn store_state(&self, env: &Env, value: &V) {
         match self.storage_type {
             StorageType::Instance => env
                 .storage()
@@ -79,6 +154,59 @@ where
         }
     }
 
+    // Set the state along with the deadline after which the state moves to
+    // the timeout state (see check_timeout and #[state_machine(..., timeout = "...")]).
+    pub fn set_state_with_deadline(&self, env: &Env, value: &V, deadline: &Deadline) {
+        self.set_state(env, value);
+        self.store_deadline(env, Some(deadline));
+    }
+
+    pub fn get_deadline(&self, env: &Env) -> Option<Deadline> {
+        let key = self.deadline_key(env);
+        match self.storage_type {
+            StorageType::Instance => env.storage().instance().get(&key),
+            StorageType::Persistent => env.storage().persistent().get(&key),
+            StorageType::Temporary => env.storage().temporary().get(&key),
+        }
+    }
+
+    // Whether the deadline of the current state has passed.
+    pub fn is_expired(&self, env: &Env) -> bool {
+        self.get_deadline(env)
+            .is_some_and(|deadline| deadline.has_passed(env))
+    }
+
+    // Move to the `timeout` state if the deadline has passed, returns whether
+    // the state moved. The move is only committed by invocations that succeed,
+    // e.g. a contract function dedicated to timeouts.
+    pub fn check_timeout(&self, env: &Env, timeout: &V) -> bool {
+        let expired = self.is_expired(env);
+        if expired {
+            self.set_state(env, timeout);
+        }
+        expired
+    }
+
+    fn deadline_key(&self, env: &Env) -> (Val, Symbol) {
+        (self.region.into_val(env), symbol_short!("deadline"))
+    }
+
+    fn store_deadline(&self, env: &Env, deadline: Option<&Deadline>) {
+        let key = self.deadline_key(env);
+        match (&self.storage_type, deadline) {
+            (StorageType::Instance, Some(deadline)) => env.storage().instance().set(&key, deadline),
+            (StorageType::Instance, None) => env.storage().instance().remove(&key),
+            (StorageType::Persistent, Some(deadline)) => {
+                env.storage().persistent().set(&key, deadline)
+            }
+            (StorageType::Persistent, None) => env.storage().persistent().remove(&key),
+            (StorageType::Temporary, Some(deadline)) => {
+                env.storage().temporary().set(&key, deadline)
+            }
+            (StorageType::Temporary, None) => env.storage().temporary().remove(&key),
+        }
+    }
+
     pub fn get_state(&self, env: &Env) -> Option<V> {
         match self.storage_type {
             StorageType::Instance => env.storage().instance().get(&self.region.into_val(env)),
@@ -87,12 +215,227 @@ where
         }
     }
 
+    pub fn try_get_state(&self, env: &Env) -> Result<V, KitError> {
+        self.get_state(env).ok_or(KitError::StateNotFound)
+    }
+
     pub fn remove_state(&self, env: &Env) {
+        self.store_deadline(env, None);
+        let previous = if self.events || self.history > 0 {
+            self.get_state(env)
+        } else {
+            None
+        };
         match self.storage_type {
             StorageType::Instance => env.storage().instance().remove(&self.region.into_val(env)),
             StorageType::Persistent => env.storage().persistent().remove(&self.region.into_val(env)),
             StorageType::Temporary => env.storage().temporary().remove(&self.region.into_val(env)),
         }
+        if previous.is_some() {
+            self.record_change(env, previous, None);
+        }
+    }
+
+    // Publish and record the state change, if enabled.
+    fn record_change(&self, env: &Env, previous: Option<V>, state: Option<V>) {
+        let previous = previous.map(|state| -> Val { state.into_val(env) });
+        let state = state.map(|state| -> Val { state.into_val(env) });
+        if self.events {
+            env.events().publish(
+                (symbol_short!("fsm"), symbol_short!("state"), self.region.clone()),
+                (previous, state),
+            );
+        }
+        if self.history > 0 {
+            let (count, len) = self.history_meta(env);
+            let function = self.function.map(|function| Symbol::new(env, function));
+            let record = (previous, state, env.ledger().sequence(), function);
+            let slot = count % self.history;
+            self.storage_set(env, &self.history_entry_key(env, slot), &record);
+            let meta = (count.wrapping_add(1), (len + 1).min(self.history));
+            self.storage_set(env, &self.history_key(env), &meta);
+        }
+    }
+
+    // Get up to `limit` recorded state changes, most recent first.
+    pub fn history(&self, env: &Env, limit: u32) -> Vec<TransitionRecord<V>> {
+        let mut history = Vec::new(env);
+        if self.history == 0 {
+            return history;
+        }
+        let (count, len) = self.history_meta(env);
+        for index in 1..=len.min(limit) {
+            let slot = count.wrapping_sub(index) % self.history;
+            if let Some(record) = self.storage_get(env, &self.history_entry_key(env, slot)) {
+                history.push_back(record);
+            }
+        }
+        history
+    }
+
+    // Extend the TTL of the state along with its deadline and history
+    // (the whole contract instance for StorageType::Instance).
+    pub fn extend_ttl(&self, env: &Env, threshold: u32, extend_to: u32) {
+        let region: Val = self.region.into_val(env);
+        match self.storage_type {
+            StorageType::Instance => env.storage().instance().extend_ttl(threshold, extend_to),
+            _ => self.extend_entry_ttl(env, &region, threshold, extend_to),
+        }
+        self.extend_entry_ttl(env, &self.deadline_key(env), threshold, extend_to);
+        if self.history > 0 {
+            self.extend_entry_ttl(env, &self.history_key(env), threshold, extend_to);
+            for slot in 0..self.history {
+                let key = self.history_entry_key(env, slot);
+                self.extend_entry_ttl(env, &key, threshold, extend_to);
+            }
+        }
+    }
+
+    fn extend_entry_ttl<Q>(&self, env: &Env, key: &Q, threshold: u32, extend_to: u32)
+    where
+        Q: IntoVal<Env, Val>,
+    {
+        match self.storage_type {
+            StorageType::Instance => {}
+            StorageType::Persistent => {
+                if env.storage().persistent().has(key) {
+                    env.storage().persistent().extend_ttl(key, threshold, extend_to);
+                }
+            }
+            StorageType::Temporary => {
+                if env.storage().temporary().has(key) {
+                    env.storage().temporary().extend_ttl(key, threshold, extend_to);
+                }
+            }
+        }
+    }
+
+    fn storage_get<Q, T>(&self, env: &Env, key: &Q) -> Option<T>
+    where
+        Q: IntoVal<Env, Val>,
+        T: TryFromVal<Env, Val>,
+    {
+        match self.storage_type {
+            StorageType::Instance => env.storage().instance().get(key),
+            StorageType::Persistent => env.storage().persistent().get(key),
+            StorageType::Temporary => env.storage().temporary().get(key),
+        }
+    }
+
+    fn storage_set<Q, T>(&self, env: &Env, key: &Q, value: &T)
+    where
+        Q: IntoVal<Env, Val>,
+        T: IntoVal<Env, Val>,
+    {
+        match self.storage_type {
+            StorageType::Instance => env.storage().instance().set(key, value),
+            StorageType::Persistent => env.storage().persistent().set(key, value),
+            StorageType::Temporary => env.storage().temporary().set(key, value),
+        }
+    }
+
+    // Number of recorded changes and number of records kept.
+    fn history_meta(&self, env: &Env) -> (u32, u32) {
+        self.storage_get(env, &self.history_key(env))
+            .unwrap_or((0, 0))
+    }
+
+    fn history_key(&self, env: &Env) -> (Val, Symbol) {
+        (self.region.into_val(env), symbol_short!("history"))
+    }
+
+    fn history_entry_key(&self, env: &Env, slot: u32) -> (Val, Symbol, u32) {
+        (self.region.into_val(env), symbol_short!("history"), slot)
+    }
+}
+
+impl<'a, K, V> StateMachine<'a, K, V>
+where
+    K: Clone + IntoVal<Env, Val> + TryFromVal<Env, Val>,
+    V: Clone + IntoVal<Env, Val> + TryFromVal<Env, Val> + TransitionTable,
+{
+    // Move to the `to` state, panics if the transition is not declared
+    // or no state is stored for the region.
+    pub fn transition(&self, env: &Env, to: &V) {
+        if let Err(error) = self.try_transition(env, to) {
+            panic_with_error!(env, error);
+        }
+    }
+
+    pub fn try_transition(&self, env: &Env, to: &V) -> Result<(), KitError> {
+        let from = self.try_get_state(env)?;
+        if !V::is_transition_allowed(&from, to) {
+            return Err(KitError::InvalidTransition);
+        }
+        self.store_state(env, to);
+        self.store_deadline(env, None);
+        self.record_change(env, Some(from), Some(to.clone()));
+        Ok(())
+    }
+}
+
+// Recorded state change (see StateMachine::with_history), None if no state
+// was stored before or after the change.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TransitionRecord<V> {
+    pub from: Option<V>,
+    pub to: Option<V>,
+    pub ledger: u32,
+    // Function guarded by #[state_machine], None for direct state changes.
+    pub function: Option<Symbol>,
+}
+
+type RecordVal = (Option<Val>, Option<Val>, u32, Option<Symbol>);
+
+impl<V: IntoVal<Env, Val> + Clone> TryFromVal<Env, TransitionRecord<V>> for Val {
+    type Error = ConversionError;
+
+    fn try_from_val(env: &Env, record: &TransitionRecord<V>) -> Result<Val, ConversionError> {
+        let record: RecordVal = (
+            record.from.clone().map(|state| state.into_val(env)),
+            record.to.clone().map(|state| state.into_val(env)),
+            record.ledger,
+            record.function.clone(),
+        );
+        Ok(record.into_val(env))
+    }
+}
+
+impl<V: TryFromVal<Env, Val>> TryFromVal<Env, Val> for TransitionRecord<V> {
+    type Error = ConversionError;
+
+    fn try_from_val(env: &Env, val: &Val) -> Result<Self, ConversionError> {
+        let (from, to, ledger, function) =
+            RecordVal::try_from_val(env, val).map_err(|_| ConversionError)?;
+        let state = |state: Option<Val>| -> Result<Option<V>, ConversionError> {
+            state
+                .map(|state| V::try_from_val(env, &state).map_err(|_| ConversionError))
+                .transpose()
+        };
+        Ok(TransitionRecord {
+            from: state(from)?,
+            to: state(to)?,
+            ledger,
+            function,
+        })
+    }
+}
+
+// Deadline of a state, passed once the ledger sequence (or timestamp)
+// is greater than the given value.
+#[contracttype]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Deadline {
+    Ledger(u32),
+    Timestamp(u64),
+}
+
+impl Deadline {
+    pub fn has_passed(&self, env: &Env) -> bool {
+        match self {
+            Deadline::Ledger(sequence) => env.ledger().sequence() > *sequence,
+            Deadline::Timestamp(timestamp) => env.ledger().timestamp() > *timestamp,
+        }
     }
 }
 
@@ -117,45 +460,123 @@ pub enum StateMachineRegion {
 // See @internal arm for state validation logic.
 #[macro_export]
 macro_rules! impl_state_machine {
-    ($instance:expr, $env:expr, $storage_type:expr, $state_enum:ident, $state_variant:ident) => {
+    ($instance:expr, $env:expr, $storage_type:expr, $state_enum:ident, $state_variant:ident
+        $(; function = $function:expr)? $(; events = $events:expr)? $(; history = $history:expr)?
+        $(; timeout = $timeout:expr)? $(; error = $error:ty)?) => {
         let state_key = $state_enum::$state_variant;
         let region_key = $crate::fsm::StateMachineRegion::Default;
-        $crate::impl_state_machine!(@internal $instance, $env, $storage_type, state_key, region_key, $state_enum, $crate::fsm::StateMachineRegion);
+        $crate::impl_state_machine!(@internal $instance, $env, $storage_type, state_key, region_key, $state_enum, $crate::fsm::StateMachineRegion,
+            [$(.with_function($function))? $(.with_events($events))? $(.with_history($history))?],
+            [$($timeout)?], ($($error)?));
     };
     ($instance:expr, $env:expr, $storage_type:expr, $state_enum:ident, $state_variant:ident, (),
-        $region_enum:ident, $region_variant:ident, ()) => {
+        $region_enum:ident, $region_variant:ident, ()
+        $(; function = $function:expr)? $(; events = $events:expr)? $(; history = $history:expr)?
+        $(; timeout = $timeout:expr)? $(; error = $error:ty)?) => {
         let state_key = $state_enum::$state_variant;
         let region_key = $region_enum::$region_variant;
-        $crate::impl_state_machine!(@internal $instance, $env, $storage_type, state_key, region_key, $state_enum, $region_enum);
+        $crate::impl_state_machine!(@internal $instance, $env, $storage_type, state_key, region_key, $state_enum, $region_enum,
+            [$(.with_function($function))? $(.with_events($events))? $(.with_history($history))?],
+            [$($timeout)?], ($($error)?));
     };
     ($instance:expr, $env:expr, $storage_type:expr, $state_enum:ident, $state_variant:ident,
-        (), $region_enum:ident, $region_variant:ident, ($($region_tuple_value:expr),+)) => {
+        (), $region_enum:ident, $region_variant:ident, ($($region_tuple_value:expr),+)
+        $(; function = $function:expr)? $(; events = $events:expr)? $(; history = $history:expr)?
+        $(; timeout = $timeout:expr)? $(; error = $error:ty)?) => {
         let state_key = $state_enum::$state_variant;
         let region_key = $region_enum::$region_variant($($region_tuple_value),*);
-        $crate::impl_state_machine!(@internal $instance, $env, $storage_type, state_key, region_key, $state_enum, $region_enum);
+        $crate::impl_state_machine!(@internal $instance, $env, $storage_type, state_key, region_key, $state_enum, $region_enum,
+            [$(.with_function($function))? $(.with_events($events))? $(.with_history($history))?],
+            [$($timeout)?], ($($error)?));
     };
-    ($instance:expr, $env:expr, $storage_type:expr, $state_enum:ident, $state_variant:ident, ($($state_tuple_value:expr),+)) => {
+    ($instance:expr, $env:expr, $storage_type:expr, $state_enum:ident, $state_variant:ident, ($($state_tuple_value:expr),+)
+        $(; function = $function:expr)? $(; events = $events:expr)? $(; history = $history:expr)?
+        $(; timeout = $timeout:expr)? $(; error = $error:ty)?) => {
         let state_key = $state_enum::$state_variant($($state_tuple_value),*);
         let region_key = $crate::fsm::StateMachineRegion::Default;
-        $crate::impl_state_machine!(@internal $instance, $env, $storage_type, state_key, region_key, $state_enum, $crate::fsm::StateMachineRegion);
+        $crate::impl_state_machine!(@internal $instance, $env, $storage_type, state_key, region_key, $state_enum, $crate::fsm::StateMachineRegion,
+            [$(.with_function($function))? $(.with_events($events))? $(.with_history($history))?],
+            [$($timeout)?], ($($error)?));
     };
     ($instance:expr, $env:expr, $storage_type:expr, $state_enum:ident, $state_variant:ident, ($($state_tuple_value:expr),+),
-        $region_enum:ident, $region_variant:ident, ()) => {
+        $region_enum:ident, $region_variant:ident, ()
+        $(; function = $function:expr)? $(; events = $events:expr)? $(; history = $history:expr)?
+        $(; timeout = $timeout:expr)? $(; error = $error:ty)?) => {
         let state_key = $state_enum::$state_variant($($state_tuple_value),*);
         let region_key = $region_enum::$region_variant;
-        $crate::impl_state_machine!(@internal $instance, $env, $storage_type, state_key, region_key, $state_enum, $region_enum);
+        $crate::impl_state_machine!(@internal $instance, $env, $storage_type, state_key, region_key, $state_enum, $region_enum,
+            [$(.with_function($function))? $(.with_events($events))? $(.with_history($history))?],
+            [$($timeout)?], ($($error)?));
     };
     ($instance:expr, $env:expr, $storage_type:expr, $state_enum:ident, $state_variant:ident,
-        ($($state_tuple_value:expr),+),$region_enum:ident, $region_variant:ident, ($($region_tuple_value:expr),+)) => {
+        ($($state_tuple_value:expr),+),$region_enum:ident, $region_variant:ident, ($($region_tuple_value:expr),+)
+        $(; function = $function:expr)? $(; events = $events:expr)? $(; history = $history:expr)?
+        $(; timeout = $timeout:expr)? $(; error = $error:ty)?) => {
         let state_key = $state_enum::$state_variant($($state_tuple_value),*);
         let region_key = $region_enum::$region_variant($($region_tuple_value),*);
-        $crate::impl_state_machine!(@internal $instance, $env, $storage_type, state_key, region_key, $state_enum, $region_enum);
+        $crate::impl_state_machine!(@internal $instance, $env, $storage_type, state_key, region_key, $state_enum, $region_enum,
+            [$(.with_function($function))? $(.with_events($events))? $(.with_history($history))?],
+            [$($timeout)?], ($($error)?));
     };
     // @internal
-    (@internal $instance:expr, $env:expr, $storage_type:expr, $state_key:expr, $region_key:expr, $state_enum:ty, $region_enum:ty) => {
-        let sm = $crate::fsm::StateMachine::<$region_enum, $state_enum>::new(&$region_key, $storage_type);
+    (@internal $instance:expr, $env:expr, $storage_type:expr, $state_key:expr, $region_key:expr, $state_enum:ty, $region_enum:ty,
+        [$($builder:tt)*], [$($timeout:expr)?], ()) => {
+        let sm = $crate::fsm::StateMachine::<$region_enum, $state_enum>::new(&$region_key, $storage_type)
+            $($builder)*;
+        $(
+            // Only functions expecting the timeout state commit the move.
+            let timeout = $timeout;
+            if sm.check_timeout(&$env, &timeout) && timeout != $state_key {
+                soroban_sdk::panic_with_error!($env, $crate::error::KitError::StateExpired);
+            }
+        )?
         $instance.on_guard($env, &sm);
-        assert_eq!(sm.get_state(&$env).unwrap(), $state_key);
+        match sm.try_get_state(&$env) {
+            Ok(state) if state == $state_key => {}
+            Ok(_) => soroban_sdk::panic_with_error!($env, $crate::error::KitError::StateMismatch),
+            Err(error) => soroban_sdk::panic_with_error!($env, error),
+        }
         $instance.on_effect($env, &sm);
     };
+    // Fallible mode: guard, state validation and effect errors are returned as `$error`
+    // (which must implement From<KitError>) from the annotated function.
+    (@internal $instance:expr, $env:expr, $storage_type:expr, $state_key:expr, $region_key:expr, $state_enum:ty, $region_enum:ty,
+        [$($builder:tt)*], [$($timeout:expr)?], ($error:ty)) => {
+        let sm = $crate::fsm::StateMachine::<$region_enum, $state_enum>::new(&$region_key, $storage_type)
+            $($builder)*;
+        $(
+            // Only functions expecting the timeout state commit the move.
+            let timeout = $timeout;
+            if sm.check_timeout(&$env, &timeout) && timeout != $state_key {
+                return Err(<$error>::from($crate::error::KitError::StateExpired));
+            }
+        )?
+        let guard: Result<(), $error> = $instance.on_guard($env, &sm);
+        guard?;
+        match sm.try_get_state(&$env) {
+            Ok(state) if state == $state_key => {}
+            Ok(_) => return Err(<$error>::from($crate::error::KitError::StateMismatch)),
+            Err(error) => return Err(<$error>::from(error)),
+        }
+        let effect: Result<(), $error> = $instance.on_effect($env, &sm);
+        effect?;
+    };
+}
+
+// Declare the transition table of a state enum, e.g.:
+// transitions!(Phase; Start -> Committing, Committing -> Revealing | Start).
+// Edges match variants regardless of their tuple values.
+#[macro_export]
+macro_rules! transitions {
+    ($state_enum:ident; $($from:ident -> $($to:ident)|+),* $(,)?) => {
+        impl $crate::fsm::TransitionTable for $state_enum {
+            fn is_transition_allowed(from: &Self, to: &Self) -> bool {
+                #[allow(unreachable_patterns)]
+                match (from, to) {
+                    $(($state_enum::$from { .. }, $($state_enum::$to { .. })|+) => true,)*
+                    _ => false,
+                }
+            }
+        }
+    };
 }
@@ -12,6 +12,8 @@
 
 #![no_std]
 
+pub mod error;
+
 #[cfg(feature = "oracle")]
 pub mod oracle;
 
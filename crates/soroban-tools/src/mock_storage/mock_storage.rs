@@ -10,147 +10,424 @@
     Author: Fred Kyung-jin Rezeau <fred@litemint.com>
 */
 
-use soroban_sdk::{Env, IntoVal, TryFromVal, Val};
+extern crate std;
 
-pub fn with_instance_storage<F, T>(_env: &Env, f: F) -> T
+use core::cell::{Cell, RefCell};
+use std::{collections::BTreeMap, thread_local};
+
+#[cfg(feature = "testutils")]
+use soroban_sdk::testutils::Ledger;
+use soroban_sdk::{symbol_short, xdr::ScVal, Env, IntoVal, TryFromVal, Val, Vec};
+
+pub fn with_instance_storage<F, T>(env: &Env, f: F) -> T
 where
     F: FnOnce(&MockStorageInstance) -> T,
 {
-    f(&MockStorageInstance::new())
+    f(&MockStorageInstance::new(env))
 }
 
-pub fn with_persistent_storage<F, T>(_env: &Env, f: F) -> T
+pub fn with_persistent_storage<F, T>(env: &Env, f: F) -> T
 where
     F: FnOnce(&MockStoragePersistent) -> T,
 {
-    f(&MockStoragePersistent::new())
+    f(&MockStoragePersistent::new(env))
 }
 
-pub fn with_temporary_storage<F, T>(_env: &Env, f: F) -> T
+pub fn with_temporary_storage<F, T>(env: &Env, f: F) -> T
 where
     F: FnOnce(&MockStorageTemporary) -> T,
 {
-    f(&MockStorageTemporary::new())
+    f(&MockStorageTemporary::new(env))
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord)]
+pub enum MockStorageType {
+    Instance,
+    Persistent,
+    Temporary,
+}
+
+// State of a mock storage entry at the current ledger sequence.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MockEntryState {
+    // The entry is live for the given number of ledgers.
+    Live(u32),
+    // Persistent (or instance) entry whose TTL ran out.
+    Archived,
+    // Temporary entry whose TTL ran out.
+    Expired,
 }
 
-pub struct MockStorageInstance {}
+/// Get the state of the entry stored with the key, if any.
+pub fn get_entry_state<K>(env: &Env, storage_type: MockStorageType, key: &K) -> Option<MockEntryState>
+where
+    K: IntoVal<Env, Val>,
+{
+    let key = to_sc_val(env, key);
+    with_ledger(env, |ledger| {
+        ledger
+            .live_until(storage_type, &key)
+            .map(|live_until| entry_state(storage_type, live_until, env.ledger().sequence()))
+    })
+}
+
+/// Get the keys of all archived or expired entries for the storage type.
+pub fn get_expired_keys(env: &Env, storage_type: MockStorageType) -> Vec<Val> {
+    let sequence = env.ledger().sequence();
+    with_ledger(env, |ledger| {
+        let mut keys = Vec::new(env);
+        for ((entry_type, key), _) in ledger.entries.iter() {
+            if *entry_type == storage_type {
+                let live_until = ledger.live_until(storage_type, key).unwrap();
+                if live_until < sequence {
+                    keys.push_back(from_sc_val(env, key));
+                }
+            }
+        }
+        keys
+    })
+}
+
+/// Clear all entries stored for the environment.
+pub fn reset(env: &Env) {
+    let id = ledger_id(env);
+    LEDGERS.with(|ledgers| {
+        ledgers.borrow_mut().remove(&id);
+    });
+}
+
+pub struct MockStorageInstance {
+    env: Env,
+}
 
 impl MockStorageInstance {
-    pub fn new() -> Self {
-        MockStorageInstance {}
+    pub fn new(env: &Env) -> Self {
+        MockStorageInstance { env: env.clone() }
     }
 
-    pub fn has<K>(&self, _key: &K) -> bool
+    pub fn has<K>(&self, key: &K) -> bool
     where
         K: IntoVal<Env, Val>,
     {
-        true
+        has(&self.env, MockStorageType::Instance, key)
     }
 
-    pub fn get<K, V>(&self, _key: &K) -> Option<V>
+    pub fn get<K, V>(&self, key: &K) -> Option<V>
     where
         K: IntoVal<Env, Val>,
         V: TryFromVal<Env, Val>,
     {
-        None
+        get(&self.env, MockStorageType::Instance, key)
     }
 
-    pub fn set<K, V>(&self, _key: &K, _val: &V)
+    pub fn set<K, V>(&self, key: &K, val: &V)
     where
         K: IntoVal<Env, Val>,
         V: IntoVal<Env, Val>,
     {
+        set(&self.env, MockStorageType::Instance, key, val)
     }
 
-    pub fn extend_ttl(&self, _threshold: u32, _extend_to: u32) {}
+    // Instance entries share the contract instance TTL.
+    pub fn extend_ttl(&self, threshold: u32, extend_to: u32) {
+        let sequence = self.env.ledger().sequence();
+        with_ledger(&self.env, |ledger| {
+            ledger.instance_live_until =
+                extended(ledger.instance_live_until, sequence, threshold, extend_to);
+        });
+    }
 
-    pub fn remove<K>(&self, _key: &K)
+    pub fn remove<K>(&self, key: &K)
     where
         K: IntoVal<Env, Val>,
     {
+        remove(&self.env, MockStorageType::Instance, key)
     }
 }
 
-pub struct MockStoragePersistent {}
+pub struct MockStoragePersistent {
+    env: Env,
+}
 
 impl MockStoragePersistent {
-    pub fn new() -> Self {
-        MockStoragePersistent {}
+    pub fn new(env: &Env) -> Self {
+        MockStoragePersistent { env: env.clone() }
     }
 
-    pub fn has<K>(&self, _key: &K) -> bool
+    pub fn has<K>(&self, key: &K) -> bool
     where
         K: IntoVal<Env, Val>,
     {
-        true
+        has(&self.env, MockStorageType::Persistent, key)
     }
 
-    pub fn get<K, V>(&self, _key: &K) -> Option<V>
+    pub fn get<K, V>(&self, key: &K) -> Option<V>
     where
         K: IntoVal<Env, Val>,
         V: TryFromVal<Env, Val>,
     {
-        None
+        get(&self.env, MockStorageType::Persistent, key)
     }
 
-    pub fn set<K, V>(&self, _key: &K, _val: &V)
+    pub fn set<K, V>(&self, key: &K, val: &V)
     where
         K: IntoVal<Env, Val>,
         V: IntoVal<Env, Val>,
     {
+        set(&self.env, MockStorageType::Persistent, key, val)
     }
 
-    pub fn extend_ttl<K>(&self, _key: &K, _threshold: u32, _extend_to: u32)
+    pub fn extend_ttl<K>(&self, key: &K, threshold: u32, extend_to: u32)
     where
         K: IntoVal<Env, Val>,
     {
+        extend_ttl(&self.env, MockStorageType::Persistent, key, threshold, extend_to)
     }
 
-    pub fn remove<K>(&self, _key: &K)
+    pub fn remove<K>(&self, key: &K)
     where
         K: IntoVal<Env, Val>,
     {
+        remove(&self.env, MockStorageType::Persistent, key)
     }
 }
 
-pub struct MockStorageTemporary {}
+pub struct MockStorageTemporary {
+    env: Env,
+}
 
 impl MockStorageTemporary {
-    pub fn new() -> Self {
-        MockStorageTemporary {}
+    pub fn new(env: &Env) -> Self {
+        MockStorageTemporary { env: env.clone() }
     }
 
-    pub fn has<K>(&self, _key: &K) -> bool
+    pub fn has<K>(&self, key: &K) -> bool
     where
         K: IntoVal<Env, Val>,
     {
-        true
+        has(&self.env, MockStorageType::Temporary, key)
     }
 
-    pub fn get<K, V>(&self, _key: &K) -> Option<V>
+    pub fn get<K, V>(&self, key: &K) -> Option<V>
     where
         K: IntoVal<Env, Val>,
         V: TryFromVal<Env, Val>,
     {
-        None
+        get(&self.env, MockStorageType::Temporary, key)
     }
 
-    pub fn set<K, V>(&self, _key: &K, _val: &V)
+    pub fn set<K, V>(&self, key: &K, val: &V)
     where
         K: IntoVal<Env, Val>,
         V: IntoVal<Env, Val>,
     {
+        set(&self.env, MockStorageType::Temporary, key, val)
     }
 
-    pub fn extend_ttl<K>(&self, _key: &K, _threshold: u32, _extend_to: u32)
+    pub fn extend_ttl<K>(&self, key: &K, threshold: u32, extend_to: u32)
     where
         K: IntoVal<Env, Val>,
     {
+        extend_ttl(&self.env, MockStorageType::Temporary, key, threshold, extend_to)
     }
 
-    pub fn remove<K>(&self, _key: &K)
+    pub fn remove<K>(&self, key: &K)
     where
         K: IntoVal<Env, Val>,
     {
+        remove(&self.env, MockStorageType::Temporary, key)
+    }
+}
+
+// In-memory ledger simulating the storage of a single Env.
+// Values are held as XDR so they outlive the host objects they were created from.
+struct MockLedger {
+    instance_live_until: u32,
+    entries: BTreeMap<(MockStorageType, ScVal), MockEntry>,
+}
+
+struct MockEntry {
+    value: ScVal,
+    live_until: u32,
+}
+
+impl MockLedger {
+    fn live_until(&self, storage_type: MockStorageType, key: &ScVal) -> Option<u32> {
+        self.entries
+            .get(&(storage_type, key.clone()))
+            .map(|entry| match storage_type {
+                MockStorageType::Instance => self.instance_live_until,
+                _ => entry.live_until,
+            })
+    }
+}
+
+// Ledgers are keyed by an id stored in the instance storage of the current
+// contract on first use: each environment (and contract) has its own ledger,
+// without the environment being kept alive. Like the host storage, the mock
+// storage is therefore accessed from a contract (e.g. `env.as_contract`).
+// Ledgers of dropped environments are released on thread exit.
+fn ledger_id(env: &Env) -> u64 {
+    let key = symbol_short!("mock_id");
+    let storage = env.storage().instance();
+    storage.get(&key).unwrap_or_else(|| {
+        let id = NEXT_LEDGER_ID.with(|next| next.replace(next.get() + 1));
+        storage.set(&key, &id);
+        id
+    })
+}
+
+thread_local! {
+    static LEDGERS: RefCell<BTreeMap<u64, MockLedger>> = const { RefCell::new(BTreeMap::new()) };
+    static NEXT_LEDGER_ID: Cell<u64> = const { Cell::new(0) };
+}
+
+// Execute the closure with the mock ledger of the environment.
+fn with_ledger<F, T>(env: &Env, f: F) -> T
+where
+    F: FnOnce(&mut MockLedger) -> T,
+{
+    let id = ledger_id(env);
+    LEDGERS.with(|ledgers| {
+        let mut ledgers = ledgers.borrow_mut();
+        let ledger = ledgers.entry(id).or_insert_with(|| MockLedger {
+            instance_live_until: live_until_for(env, MockStorageType::Instance),
+            entries: BTreeMap::new(),
+        });
+        f(ledger)
+    })
+}
+
+fn has<K>(env: &Env, storage_type: MockStorageType, key: &K) -> bool
+where
+    K: IntoVal<Env, Val>,
+{
+    let key = to_sc_val(env, key);
+    with_ledger(env, |ledger| live_entry(env, ledger, storage_type, &key).is_some())
+}
+
+fn get<K, V>(env: &Env, storage_type: MockStorageType, key: &K) -> Option<V>
+where
+    K: IntoVal<Env, Val>,
+    V: TryFromVal<Env, Val>,
+{
+    let key = to_sc_val(env, key);
+    // Values of another type read as missing.
+    with_ledger(env, |ledger| live_entry(env, ledger, storage_type, &key))
+        .and_then(|value| V::try_from_val(env, &from_sc_val(env, &value)).ok())
+}
+
+fn set<K, V>(env: &Env, storage_type: MockStorageType, key: &K, val: &V)
+where
+    K: IntoVal<Env, Val>,
+    V: IntoVal<Env, Val>,
+{
+    let key = to_sc_val(env, key);
+    let value = to_sc_val(env, val);
+    with_ledger(env, |ledger| {
+        // Overwriting a live entry preserves its TTL.
+        let live_until = match live_entry(env, ledger, storage_type, &key) {
+            Some(_) => ledger.entries[&(storage_type, key.clone())].live_until,
+            None => live_until_for(env, storage_type),
+        };
+        ledger
+            .entries
+            .insert((storage_type, key), MockEntry { value, live_until });
+    });
+}
+
+fn remove<K>(env: &Env, storage_type: MockStorageType, key: &K)
+where
+    K: IntoVal<Env, Val>,
+{
+    let key = to_sc_val(env, key);
+    with_ledger(env, |ledger| {
+        ledger.entries.remove(&(storage_type, key));
+    });
+}
+
+fn extend_ttl<K>(env: &Env, storage_type: MockStorageType, key: &K, threshold: u32, extend_to: u32)
+where
+    K: IntoVal<Env, Val>,
+{
+    let key = to_sc_val(env, key);
+    let sequence = env.ledger().sequence();
+    with_ledger(env, |ledger| {
+        if live_entry(env, ledger, storage_type, &key).is_none() {
+            panic!("mock storage: extending the TTL of a missing entry");
+        }
+        let entry = ledger.entries.get_mut(&(storage_type, key)).unwrap();
+        entry.live_until = extended(entry.live_until, sequence, threshold, extend_to);
+    })
+}
+
+// Get the value of a live entry. Like the host, accessing an archived entry
+// panics while expired temporary entries are evicted.
+fn live_entry(
+    env: &Env,
+    ledger: &mut MockLedger,
+    storage_type: MockStorageType,
+    key: &ScVal,
+) -> Option<ScVal> {
+    let live_until = ledger.live_until(storage_type, key)?;
+    match entry_state(storage_type, live_until, env.ledger().sequence()) {
+        MockEntryState::Live(_) => Some(ledger.entries[&(storage_type, key.clone())].value.clone()),
+        MockEntryState::Archived => panic!("mock storage: accessing an archived entry"),
+        MockEntryState::Expired => {
+            ledger.entries.remove(&(storage_type, key.clone()));
+            None
+        }
+    }
+}
+
+fn entry_state(storage_type: MockStorageType, live_until: u32, sequence: u32) -> MockEntryState {
+    match storage_type {
+        _ if live_until >= sequence => MockEntryState::Live(live_until - sequence),
+        MockStorageType::Temporary => MockEntryState::Expired,
+        _ => MockEntryState::Archived,
+    }
+}
+
+// Live-until ledger of a new entry (minimum TTL from the ledger settings).
+fn live_until_for(env: &Env, storage_type: MockStorageType) -> u32 {
+    let min_ttl = min_ttl(env, storage_type);
+    env.ledger().sequence().saturating_add(min_ttl.saturating_sub(1))
+}
+
+#[cfg(feature = "testutils")]
+fn min_ttl(env: &Env, storage_type: MockStorageType) -> u32 {
+    let info = env.ledger().get();
+    match storage_type {
+        MockStorageType::Temporary => info.min_temp_entry_ttl,
+        _ => info.min_persistent_entry_ttl,
+    }
+}
+
+// Default ledger settings of the test environment.
+#[cfg(not(feature = "testutils"))]
+fn min_ttl(_env: &Env, storage_type: MockStorageType) -> u32 {
+    match storage_type {
+        MockStorageType::Temporary => 16,
+        _ => 4096,
     }
 }
+
+// Extend the live-until ledger iff the TTL is below the threshold.
+fn extended(live_until: u32, sequence: u32, threshold: u32, extend_to: u32) -> u32 {
+    if live_until.saturating_sub(sequence) < threshold {
+        live_until.max(sequence.saturating_add(extend_to))
+    } else {
+        live_until
+    }
+}
+
+fn to_sc_val<V>(env: &Env, val: &V) -> ScVal
+where
+    V: IntoVal<Env, Val>,
+{
+    ScVal::try_from_val(env, &val.into_val(env)).unwrap()
+}
+
+fn from_sc_val(env: &Env, val: &ScVal) -> Val {
+    Val::try_from_val(env, val).unwrap()
+}
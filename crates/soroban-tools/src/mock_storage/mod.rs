@@ -10,9 +10,10 @@
     Author: Fred Kyung-jin Rezeau <fred@litemint.com>
 */
 
-/// A rudimentary mock storage allowing testing and profiling.
-/// outside of Soroban environment.
+/// An in-memory mock storage (per tier, per Env and contract) with TTL simulation
+/// allowing testing and profiling outside of Soroban environment.
 /// `cargo test --features mock-storage`
+/// (with `testutils` for the Env ledger settings).
 #[cfg(feature = "mock-storage")]
 mod mock_storage;
 
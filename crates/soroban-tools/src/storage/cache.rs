@@ -0,0 +1,216 @@
+/*
+    Copyright (c) 2023-2024 Frederic Kyung-jin Rezeau (오경진 吳景振)
+
+    This file is part of soroban-kit.
+
+    Licensed under the MIT License, this software is provided "AS IS",
+    no liability assumed. For details, see the LICENSE file in the
+    root directory.
+
+    Author: Fred Kyung-jin Rezeau <fred@litemint.com>
+*/
+
+use soroban_sdk::{Env, IntoVal, Map, TryFromVal, Val};
+
+use super::{
+    from_versioned_val, tier_extend_ttl, tier_remove, tier_set, to_versioned_val, Indexed,
+    StorageOps, StorageProxy, StorageTier, Versioned,
+};
+
+// Cached entry: stored data (None if missing), dirty flag, TTL extension
+// (threshold, extend_to) to apply when flushed and whether the TTL was
+// already extended on read.
+type CacheEntry = (Option<Val>, bool, Option<(u32, u32)>, bool);
+
+/// Write-back cache over the typed storage, scoped to a contract invocation.
+///
+/// Reads are memoized, writes are coalesced and dirty entries are written
+/// once on `flush` or when the cache is dropped. TTL policies extending on
+/// read apply once per key. Data types with secondary indexes, storage
+/// events, logical expiry or registered keys are written through.
+pub struct Cache {
+    env: Env,
+    entries: Map<(u32, Val), CacheEntry>,
+}
+
+impl Cache {
+    pub fn new(env: &Env) -> Self {
+        Cache {
+            env: env.clone(),
+            entries: Map::new(env),
+        }
+    }
+
+    fn cache_key<K, T>(&self, key: &K) -> (u32, Val)
+    where
+        K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+        for<'a> StorageProxy<'a, K, T>: StorageOps<T>,
+    {
+        (
+            <StorageProxy<K, T> as StorageOps<T>>::TIER as u32,
+            key.into_val(&self.env),
+        )
+    }
+
+    fn encode<T: Versioned>(&self, data: &T) -> Val {
+        if T::VERSION == 0 {
+            data.into_val(&self.env)
+        } else {
+            to_versioned_val(&self.env, data)
+        }
+    }
+
+    fn decode<T: Versioned>(&self, val: Val) -> T {
+        if T::VERSION == 0 {
+            T::try_from_val(&self.env, &val).unwrap()
+        } else {
+            from_versioned_val(&self.env, val).0
+        }
+    }
+
+    // Indexes, events and key registries must observe every write, and
+    // expiring data is stored along with the ledger of the write.
+    fn write_through<K, T>() -> bool
+    where
+        K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+        T: Indexed,
+        for<'a> StorageProxy<'a, K, T>: StorageOps<T>,
+    {
+        !T::INDEX.is_empty()
+            || <StorageProxy<K, T> as StorageOps<T>>::EVENTS.is_some()
+            || <StorageProxy<K, T> as StorageOps<T>>::EXPIRY.is_some()
+            || <StorageProxy<K, T> as StorageOps<T>>::REGISTRY.is_some()
+    }
+
+    fn write<K, T>(&mut self, key: &K, data: Option<Val>)
+    where
+        K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+        for<'a> StorageProxy<'a, K, T>: StorageOps<T>,
+    {
+        let ttl = <StorageProxy<K, T> as StorageOps<T>>::TTL
+            .filter(|ttl| ttl.extend_on_write)
+            .map(|ttl| (ttl.threshold, ttl.extend_to));
+        let cache_key = self.cache_key::<K, T>(key);
+        let extended = self.extended(cache_key);
+        self.entries.set(cache_key, (data, true, ttl, extended));
+    }
+
+    fn extended(&self, cache_key: (u32, Val)) -> bool {
+        self.entries
+            .get(cache_key)
+            .is_some_and(|(_, _, _, extended)| extended)
+    }
+
+    pub fn get<K, T>(&mut self, key: &K) -> Option<T>
+    where
+        K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+        T: Versioned,
+        for<'a> StorageProxy<'a, K, T>: StorageOps<T>,
+    {
+        let cache_key = self.cache_key::<K, T>(key);
+        match self.entries.get(cache_key) {
+            Some((data, dirty, ttl, extended)) => {
+                let read_ttl = <StorageProxy<K, T> as StorageOps<T>>::TTL
+                    .filter(|ttl| ttl.extend_on_read && data.is_some() && !extended);
+                if let Some(read_ttl) = read_ttl {
+                    // Clean entries are stored, dirty entries are extended when flushed.
+                    let ttl = if dirty {
+                        Some((read_ttl.threshold, read_ttl.extend_to))
+                    } else {
+                        StorageProxy::new(key).extend_ttl(
+                            &self.env,
+                            read_ttl.threshold,
+                            read_ttl.extend_to,
+                        );
+                        ttl
+                    };
+                    self.entries.set(cache_key, (data, dirty, ttl, true));
+                }
+                data.map(|val| self.decode(val))
+            }
+            None => {
+                // The typed storage extends the TTL of the data read.
+                let data = StorageProxy::new(key).get(&self.env);
+                let val = data.as_ref().map(|data| self.encode(data));
+                let extended = data.is_some();
+                self.entries.set(cache_key, (val, false, None, extended));
+                data
+            }
+        }
+    }
+
+    pub fn set<K, T>(&mut self, key: &K, data: &T)
+    where
+        K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+        T: Versioned + Indexed,
+        for<'a> StorageProxy<'a, K, T>: StorageOps<T>,
+    {
+        let val = self.encode(data);
+        if Self::write_through::<K, T>() {
+            StorageProxy::new(key).set(&self.env, data);
+            let cache_key = self.cache_key::<K, T>(key);
+            let extended = self.extended(cache_key);
+            self.entries
+                .set(cache_key, (Some(val), false, None, extended));
+        } else {
+            self.write::<K, T>(key, Some(val));
+        }
+    }
+
+    pub fn has<K, T>(&self, key: &K) -> bool
+    where
+        K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+        for<'a> StorageProxy<'a, K, T>: StorageOps<T>,
+    {
+        match self.entries.get(self.cache_key::<K, T>(key)) {
+            Some((data, _, _, _)) => data.is_some(),
+            None => StorageProxy::new(key).has(&self.env),
+        }
+    }
+
+    pub fn remove<K, T>(&mut self, key: &K)
+    where
+        K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+        T: Indexed,
+        for<'a> StorageProxy<'a, K, T>: StorageOps<T>,
+    {
+        if Self::write_through::<K, T>() {
+            StorageProxy::new(key).remove(&self.env);
+            let cache_key = self.cache_key::<K, T>(key);
+            let extended = self.extended(cache_key);
+            self.entries.set(cache_key, (None, false, None, extended));
+        } else {
+            self.write::<K, T>(key, None);
+        }
+    }
+
+    /// Write the dirty entries to the storage.
+    pub fn flush(&mut self) {
+        for ((tier, key), (data, dirty, ttl, extended)) in self.entries.iter() {
+            if !dirty {
+                continue;
+            }
+            let storage_tier = match tier {
+                0 => StorageTier::Instance,
+                1 => StorageTier::Persistent,
+                _ => StorageTier::Temporary,
+            };
+            match data {
+                Some(data) => {
+                    tier_set(&self.env, storage_tier, &key, &data);
+                    if let Some((threshold, extend_to)) = ttl {
+                        tier_extend_ttl(&self.env, storage_tier, &key, threshold, extend_to);
+                    }
+                }
+                None => tier_remove(&self.env, storage_tier, &key),
+            }
+            self.entries.set((tier, key), (data, false, None, extended));
+        }
+    }
+}
+
+impl Drop for Cache {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
@@ -0,0 +1,686 @@
+/*
+    Copyright (c) 2023-2024 Frederic Kyung-jin Rezeau (오경진 吳景振)
+
+    This file is part of soroban-kit.
+
+    Licensed under the MIT License, this software is provided "AS IS",
+    no liability assumed. For details, see the LICENSE file in the
+    root directory.
+
+    Author: Fred Kyung-jin Rezeau <fred@litemint.com>
+*/
+
+use soroban_sdk::{
+    panic_with_error, symbol_short, Env, IntoVal, Symbol, TryFromVal, TryIntoVal, Val, Vec,
+};
+
+use core::marker::PhantomData;
+
+use super::{
+    tier_bump, tier_extend_ttl, tier_get, tier_remove, tier_set, StorageOps, StorageProxy,
+    StorageTier, TtlPolicy,
+};
+use crate::error::KitError;
+
+fn len_key<K: Clone>(key: &K) -> (K, Symbol, u32) {
+    (key.clone(), symbol_short!("len"), 0)
+}
+
+/// Chunked vector spreading its elements across derived storage entries.
+///
+/// Elements are stored under `(key, index)` and the length under
+/// `(key, "len", 0)`, all in the tier of the data type `T` and subject
+/// to its TTL policy (see `impl_storage!`).
+pub struct StorageVec<'a, K, T> {
+    key: &'a K,
+    _data: PhantomData<*const T>,
+}
+
+impl<'a, K, T> StorageVec<'a, K, T>
+where
+    K: Clone + IntoVal<Env, Val> + TryIntoVal<Env, Val> + TryFromVal<Env, Val>,
+    T: IntoVal<Env, Val> + TryIntoVal<Env, Val> + TryFromVal<Env, Val>,
+    for<'b> StorageProxy<'b, (K, u32), T>: StorageOps<T>,
+{
+    pub fn new(key: &'a K) -> Self {
+        StorageVec {
+            key,
+            _data: PhantomData,
+        }
+    }
+
+    pub fn get_key(&self) -> &'a K {
+        self.key
+    }
+
+    fn element_key(&self, index: u32) -> (K, u32) {
+        (self.key.clone(), index)
+    }
+
+    fn tier() -> StorageTier {
+        <StorageProxy<(K, u32), T> as StorageOps<T>>::TIER
+    }
+
+    fn ttl() -> Option<TtlPolicy> {
+        <StorageProxy<(K, u32), T> as StorageOps<T>>::TTL
+    }
+
+    fn set_len(&self, env: &Env, len: u32) {
+        let key = len_key(self.key);
+        if len == 0 {
+            tier_remove(env, Self::tier(), &key);
+        } else {
+            tier_set(env, Self::tier(), &key, &len);
+            tier_bump(env, Self::tier(), &key, Self::ttl(), true);
+        }
+    }
+
+    pub fn len(&self, env: &Env) -> u32 {
+        tier_get(env, Self::tier(), &len_key(self.key)).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self, env: &Env) -> bool {
+        self.len(env) == 0
+    }
+
+    pub fn get(&self, env: &Env, index: u32) -> Option<T> {
+        let data = StorageProxy::new(&self.element_key(index)).get(env);
+        if data.is_some() {
+            tier_bump(env, Self::tier(), &len_key(self.key), Self::ttl(), false);
+        }
+        data
+    }
+
+    /// Replace the element at `index` or `KitError::OutOfBounds`.
+    pub fn set(&self, env: &Env, index: u32, data: &T) -> Result<(), KitError> {
+        if index >= self.len(env) {
+            return Err(KitError::OutOfBounds);
+        }
+        StorageProxy::new(&self.element_key(index)).set(env, data);
+        tier_bump(env, Self::tier(), &len_key(self.key), Self::ttl(), true);
+        Ok(())
+    }
+
+    /// Append an element and return its index.
+    pub fn push(&self, env: &Env, data: &T) -> u32 {
+        let len = self.len(env);
+        StorageProxy::new(&self.element_key(len)).set(env, data);
+        self.set_len(env, len + 1);
+        len
+    }
+
+    /// Remove and return the last element.
+    pub fn pop(&self, env: &Env) -> Option<T> {
+        let len = self.len(env);
+        if len == 0 {
+            return None;
+        }
+        let key = self.element_key(len - 1);
+        let proxy = StorageProxy::new(&key);
+        let data = proxy.get(env);
+        proxy.remove(env);
+        self.set_len(env, len - 1);
+        data
+    }
+
+    /// Get up to `limit` elements starting from index `start`.
+    pub fn page(&self, env: &Env, start: u32, limit: u32) -> Vec<T> {
+        let end = self.len(env).min(start.saturating_add(limit));
+        let mut page = Vec::new(env);
+        for index in start..end {
+            if let Some(data) = StorageProxy::new(&self.element_key(index)).get(env) {
+                page.push_back(data);
+            }
+        }
+        page
+    }
+
+    /// Extend the TTL of the element at `index` (and of the vector length)
+    /// or `KitError::OutOfBounds`.
+    pub fn extend_ttl(
+        &self,
+        env: &Env,
+        index: u32,
+        threshold: u32,
+        extend_to: u32,
+    ) -> Result<(), KitError> {
+        if index >= self.len(env) {
+            return Err(KitError::OutOfBounds);
+        }
+        StorageProxy::new(&self.element_key(index)).extend_ttl(env, threshold, extend_to);
+        tier_extend_ttl(env, Self::tier(), &len_key(self.key), threshold, extend_to);
+        Ok(())
+    }
+
+    /// Remove all the elements.
+    pub fn clear(&self, env: &Env) {
+        for index in 0..self.len(env) {
+            StorageProxy::new(&self.element_key(index)).remove(env);
+        }
+        self.set_len(env, 0);
+    }
+}
+
+/// Chunked map spreading its entries across derived storage entries.
+///
+/// Values are stored under `(key, map_key)` through the typed storage of the
+/// data type `T` (see `StorageOps`). Keys are listed under `(key, "key", slot)`
+/// to allow paginated iteration, with the slot of each key stored under
+/// `(key, "slot", map_key)`. The length is stored under `(key, "len", 0)`.
+/// All entries use the tier of the data type `T` and are subject to its TTL
+/// policy (see `impl_storage!`).
+pub struct StorageMap<'a, K, MK, T> {
+    key: &'a K,
+    _data: PhantomData<*const (MK, T)>,
+}
+
+impl<'a, K, MK, T> StorageMap<'a, K, MK, T>
+where
+    K: Clone + IntoVal<Env, Val> + TryIntoVal<Env, Val> + TryFromVal<Env, Val>,
+    MK: Clone + IntoVal<Env, Val> + TryIntoVal<Env, Val> + TryFromVal<Env, Val>,
+    T: IntoVal<Env, Val> + TryIntoVal<Env, Val> + TryFromVal<Env, Val>,
+    for<'b> StorageProxy<'b, (K, MK), T>: StorageOps<T>,
+{
+    pub fn new(key: &'a K) -> Self {
+        StorageMap {
+            key,
+            _data: PhantomData,
+        }
+    }
+
+    pub fn get_key(&self) -> &'a K {
+        self.key
+    }
+
+    fn value_key(&self, map_key: &MK) -> (K, MK) {
+        (self.key.clone(), map_key.clone())
+    }
+
+    fn slot_key(&self, slot: u32) -> (K, Symbol, u32) {
+        (self.key.clone(), symbol_short!("key"), slot)
+    }
+
+    fn index_key(&self, map_key: &MK) -> (K, Symbol, MK) {
+        (self.key.clone(), symbol_short!("slot"), map_key.clone())
+    }
+
+    fn tier() -> StorageTier {
+        <StorageProxy<(K, MK), T> as StorageOps<T>>::TIER
+    }
+
+    fn ttl() -> Option<TtlPolicy> {
+        <StorageProxy<(K, MK), T> as StorageOps<T>>::TTL
+    }
+
+    fn get_slot(&self, env: &Env, map_key: &MK) -> Option<u32> {
+        tier_get(env, Self::tier(), &self.index_key(map_key))
+    }
+
+    // The value TTL is handled by the typed storage.
+    fn bump(&self, env: &Env, map_key: &MK, slot: u32, write: bool) {
+        tier_bump(
+            env,
+            Self::tier(),
+            &self.index_key(map_key),
+            Self::ttl(),
+            write,
+        );
+        tier_bump(env, Self::tier(), &self.slot_key(slot), Self::ttl(), write);
+        tier_bump(env, Self::tier(), &len_key(self.key), Self::ttl(), write);
+    }
+
+    fn set_len(&self, env: &Env, len: u32) {
+        let key = len_key(self.key);
+        if len == 0 {
+            tier_remove(env, Self::tier(), &key);
+        } else {
+            tier_set(env, Self::tier(), &key, &len);
+        }
+    }
+
+    pub fn len(&self, env: &Env) -> u32 {
+        tier_get(env, Self::tier(), &len_key(self.key)).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self, env: &Env) -> bool {
+        self.len(env) == 0
+    }
+
+    pub fn has(&self, env: &Env, map_key: &MK) -> bool {
+        StorageProxy::new(&self.value_key(map_key)).has(env)
+    }
+
+    pub fn get(&self, env: &Env, map_key: &MK) -> Option<T> {
+        let data = StorageProxy::new(&self.value_key(map_key)).get(env)?;
+        if let Some(slot) = self.get_slot(env, map_key) {
+            self.bump(env, map_key, slot, false);
+        }
+        Some(data)
+    }
+
+    /// Insert or replace the value for `map_key`.
+    pub fn set(&self, env: &Env, map_key: &MK, data: &T) {
+        let slot = match self.get_slot(env, map_key) {
+            Some(slot) => slot,
+            None => {
+                let len = self.len(env);
+                tier_set(env, Self::tier(), &self.slot_key(len), map_key);
+                tier_set(env, Self::tier(), &self.index_key(map_key), &len);
+                self.set_len(env, len + 1);
+                len
+            }
+        };
+        StorageProxy::new(&self.value_key(map_key)).set(env, data);
+        self.bump(env, map_key, slot, true);
+    }
+
+    /// Remove the value for `map_key`, returns false if missing.
+    pub fn remove(&self, env: &Env, map_key: &MK) -> bool {
+        let Some(slot) = self.get_slot(env, map_key) else {
+            return false;
+        };
+        let last = self.len(env) - 1;
+        if slot != last {
+            // Move the last key into the freed slot.
+            let last_key: MK = tier_get(env, Self::tier(), &self.slot_key(last)).unwrap();
+            tier_set(env, Self::tier(), &self.slot_key(slot), &last_key);
+            tier_set(env, Self::tier(), &self.index_key(&last_key), &slot);
+        }
+        tier_remove(env, Self::tier(), &self.slot_key(last));
+        tier_remove(env, Self::tier(), &self.index_key(map_key));
+        StorageProxy::new(&self.value_key(map_key)).remove(env);
+        self.set_len(env, last);
+        true
+    }
+
+    /// Get up to `limit` keys starting from slot `start`.
+    pub fn keys(&self, env: &Env, start: u32, limit: u32) -> Vec<MK> {
+        let end = self.len(env).min(start.saturating_add(limit));
+        let mut keys = Vec::new(env);
+        for slot in start..end {
+            if let Some(map_key) = tier_get(env, Self::tier(), &self.slot_key(slot)) {
+                keys.push_back(map_key);
+            }
+        }
+        keys
+    }
+
+    /// Get up to `limit` entries starting from slot `start`.
+    pub fn page(&self, env: &Env, start: u32, limit: u32) -> Vec<(MK, T)> {
+        let mut page = Vec::new(env);
+        for map_key in self.keys(env, start, limit) {
+            if let Some(data) = self.get(env, &map_key) {
+                page.push_back((map_key, data));
+            }
+        }
+        page
+    }
+
+    /// Extend the TTL of the entry for `map_key` (and of the map length)
+    /// or `KitError::NotFound`.
+    pub fn extend_ttl(
+        &self,
+        env: &Env,
+        map_key: &MK,
+        threshold: u32,
+        extend_to: u32,
+    ) -> Result<(), KitError> {
+        let slot = self.get_slot(env, map_key).ok_or(KitError::NotFound)?;
+        StorageProxy::new(&self.value_key(map_key)).try_extend_ttl(env, threshold, extend_to)?;
+        tier_extend_ttl(
+            env,
+            Self::tier(),
+            &self.index_key(map_key),
+            threshold,
+            extend_to,
+        );
+        tier_extend_ttl(
+            env,
+            Self::tier(),
+            &self.slot_key(slot),
+            threshold,
+            extend_to,
+        );
+        tier_extend_ttl(env, Self::tier(), &len_key(self.key), threshold, extend_to);
+        Ok(())
+    }
+}
+
+fn head_key<K: Clone>(key: &K) -> (K, Symbol, u32) {
+    (key.clone(), symbol_short!("head"), 0)
+}
+
+/// Double-ended queue spreading its elements across derived storage entries.
+///
+/// Elements are stored under `(key, slot)` in a ring of `u32` slots, with
+/// the slot of the front element stored under `(key, "head", 0)` and the
+/// length under `(key, "len", 0)`. All entries use the tier of the data
+/// type `T` and are subject to its TTL policy (see `impl_storage!`).
+pub struct StorageDeque<'a, K, T> {
+    key: &'a K,
+    _data: PhantomData<*const T>,
+}
+
+impl<'a, K, T> StorageDeque<'a, K, T>
+where
+    K: Clone + IntoVal<Env, Val> + TryIntoVal<Env, Val> + TryFromVal<Env, Val>,
+    T: IntoVal<Env, Val> + TryIntoVal<Env, Val> + TryFromVal<Env, Val>,
+    for<'b> StorageProxy<'b, (K, u32), T>: StorageOps<T>,
+{
+    pub fn new(key: &'a K) -> Self {
+        StorageDeque {
+            key,
+            _data: PhantomData,
+        }
+    }
+
+    pub fn get_key(&self) -> &'a K {
+        self.key
+    }
+
+    fn element_key(&self, slot: u32) -> (K, u32) {
+        (self.key.clone(), slot)
+    }
+
+    fn tier() -> StorageTier {
+        <StorageProxy<(K, u32), T> as StorageOps<T>>::TIER
+    }
+
+    fn ttl() -> Option<TtlPolicy> {
+        <StorageProxy<(K, u32), T> as StorageOps<T>>::TTL
+    }
+
+    fn head(&self, env: &Env) -> u32 {
+        tier_get(env, Self::tier(), &head_key(self.key)).unwrap_or(0)
+    }
+
+    fn set_bounds(&self, env: &Env, head: u32, len: u32) {
+        let (head_key, len_key) = (head_key(self.key), len_key(self.key));
+        if len == 0 {
+            tier_remove(env, Self::tier(), &head_key);
+            tier_remove(env, Self::tier(), &len_key);
+        } else {
+            tier_set(env, Self::tier(), &head_key, &head);
+            tier_set(env, Self::tier(), &len_key, &len);
+            tier_bump(env, Self::tier(), &head_key, Self::ttl(), true);
+            tier_bump(env, Self::tier(), &len_key, Self::ttl(), true);
+        }
+    }
+
+    fn take(&self, env: &Env, slot: u32) -> Option<T> {
+        let key = self.element_key(slot);
+        let proxy = StorageProxy::new(&key);
+        let data = proxy.get(env)?;
+        proxy.remove(env);
+        Some(data)
+    }
+
+    pub fn len(&self, env: &Env) -> u32 {
+        tier_get(env, Self::tier(), &len_key(self.key)).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self, env: &Env) -> bool {
+        self.len(env) == 0
+    }
+
+    /// Get the element at `index` from the front.
+    pub fn get(&self, env: &Env, index: u32) -> Option<T> {
+        if index >= self.len(env) {
+            return None;
+        }
+        let slot = self.head(env).wrapping_add(index);
+        StorageProxy::new(&self.element_key(slot)).get(env)
+    }
+
+    pub fn front(&self, env: &Env) -> Option<T> {
+        self.get(env, 0)
+    }
+
+    pub fn back(&self, env: &Env) -> Option<T> {
+        self.get(env, self.len(env).checked_sub(1)?)
+    }
+
+    pub fn push_back(&self, env: &Env, data: &T) {
+        let (head, len) = (self.head(env), self.len(env));
+        StorageProxy::new(&self.element_key(head.wrapping_add(len))).set(env, data);
+        self.set_bounds(env, head, len + 1);
+    }
+
+    pub fn push_front(&self, env: &Env, data: &T) {
+        let head = self.head(env).wrapping_sub(1);
+        StorageProxy::new(&self.element_key(head)).set(env, data);
+        self.set_bounds(env, head, self.len(env) + 1);
+    }
+
+    /// Remove and return the front element, None if empty or the element
+    /// is missing (e.g. when temporary entries expired), leaving the deque unchanged.
+    pub fn pop_front(&self, env: &Env) -> Option<T> {
+        let len = self.len(env).checked_sub(1)?;
+        let head = self.head(env);
+        let data = self.take(env, head)?;
+        self.set_bounds(env, head.wrapping_add(1), len);
+        Some(data)
+    }
+
+    /// Remove and return the back element, None if empty or the element
+    /// is missing, leaving the deque unchanged.
+    pub fn pop_back(&self, env: &Env) -> Option<T> {
+        let len = self.len(env).checked_sub(1)?;
+        let head = self.head(env);
+        let data = self.take(env, head.wrapping_add(len))?;
+        self.set_bounds(env, head, len);
+        Some(data)
+    }
+
+    /// Get up to `limit` elements starting from index `start` (from the front).
+    pub fn page(&self, env: &Env, start: u32, limit: u32) -> Vec<T> {
+        let end = self.len(env).min(start.saturating_add(limit));
+        let head = self.head(env);
+        let mut page = Vec::new(env);
+        for index in start..end {
+            let key = self.element_key(head.wrapping_add(index));
+            if let Some(data) = StorageProxy::new(&key).get(env) {
+                page.push_back(data);
+            }
+        }
+        page
+    }
+
+    /// Remove all the elements.
+    pub fn clear(&self, env: &Env) {
+        let head = self.head(env);
+        for index in 0..self.len(env) {
+            StorageProxy::new(&self.element_key(head.wrapping_add(index))).remove(env);
+        }
+        self.set_bounds(env, 0, 0);
+    }
+}
+
+/// Binary max-heap spreading its elements across derived storage entries.
+///
+/// Elements are stored under `(key, index)` in heap order and the length
+/// under `(key, "len", 0)`, all in the tier of the data type `T` and subject
+/// to its TTL policy (see `impl_storage!`). Pushing and popping read and
+/// write O(log n) entries.
+pub struct StorageHeap<'a, K, T> {
+    key: &'a K,
+    _data: PhantomData<*const T>,
+}
+
+impl<'a, K, T> StorageHeap<'a, K, T>
+where
+    K: Clone + IntoVal<Env, Val> + TryIntoVal<Env, Val> + TryFromVal<Env, Val>,
+    T: Ord + IntoVal<Env, Val> + TryIntoVal<Env, Val> + TryFromVal<Env, Val>,
+    for<'b> StorageProxy<'b, (K, u32), T>: StorageOps<T>,
+{
+    pub fn new(key: &'a K) -> Self {
+        StorageHeap {
+            key,
+            _data: PhantomData,
+        }
+    }
+
+    pub fn get_key(&self) -> &'a K {
+        self.key
+    }
+
+    fn element_key(&self, index: u32) -> (K, u32) {
+        (self.key.clone(), index)
+    }
+
+    fn tier() -> StorageTier {
+        <StorageProxy<(K, u32), T> as StorageOps<T>>::TIER
+    }
+
+    fn ttl() -> Option<TtlPolicy> {
+        <StorageProxy<(K, u32), T> as StorageOps<T>>::TTL
+    }
+
+    fn set_len(&self, env: &Env, len: u32) {
+        let key = len_key(self.key);
+        if len == 0 {
+            tier_remove(env, Self::tier(), &key);
+        } else {
+            tier_set(env, Self::tier(), &key, &len);
+            tier_bump(env, Self::tier(), &key, Self::ttl(), true);
+        }
+    }
+
+    // Elements may be missing, e.g. when temporary entries expired.
+    fn read(&self, env: &Env, index: u32) -> Result<T, KitError> {
+        StorageProxy::new(&self.element_key(index))
+            .get(env)
+            .ok_or(KitError::NotFound)
+    }
+
+    fn write(&self, env: &Env, index: u32, data: &T) {
+        StorageProxy::new(&self.element_key(index)).set(env, data);
+    }
+
+    pub fn len(&self, env: &Env) -> u32 {
+        tier_get(env, Self::tier(), &len_key(self.key)).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self, env: &Env) -> bool {
+        self.len(env) == 0
+    }
+
+    /// Get the greatest element.
+    pub fn peek(&self, env: &Env) -> Option<T> {
+        StorageProxy::new(&self.element_key(0)).get(env)
+    }
+
+    /// Push the element, panics if an element is missing (see `try_push`).
+    pub fn push(&self, env: &Env, data: &T) {
+        if let Err(error) = self.try_push(env, data) {
+            panic_with_error!(env, error);
+        }
+    }
+
+    /// Push the element or `KitError::NotFound` if an element is missing,
+    /// leaving the heap unchanged.
+    pub fn try_push(&self, env: &Env, data: &T) -> Result<(), KitError> {
+        let len = self.len(env);
+
+        // Read the smaller parents before writing anything.
+        let mut parents = Vec::new(env);
+        let mut index = len;
+        while index > 0 {
+            let parent_index = (index - 1) / 2;
+            let parent = self.read(env, parent_index)?;
+            if parent >= *data {
+                break;
+            }
+            parents.push_back(parent);
+            index = parent_index;
+        }
+
+        // Move them down into the hole left for the element.
+        let mut index = len;
+        for position in 0..parents.len() {
+            self.write(env, index, &parents.get_unchecked(position));
+            index = (index - 1) / 2;
+        }
+        self.write(env, index, data);
+        self.set_len(env, len + 1);
+        Ok(())
+    }
+
+    /// Remove and return the greatest element, panics if an element is
+    /// missing (see `try_pop`).
+    pub fn pop(&self, env: &Env) -> Option<T> {
+        match self.try_pop(env) {
+            Ok(top) => top,
+            Err(error) => panic_with_error!(env, error),
+        }
+    }
+
+    /// Remove and return the greatest element, None if empty or
+    /// `KitError::NotFound` if an element is missing, leaving the heap unchanged.
+    pub fn try_pop(&self, env: &Env) -> Result<Option<T>, KitError> {
+        let Some(len) = self.len(env).checked_sub(1) else {
+            return Ok(None);
+        };
+        let top = self.read(env, 0)?;
+        let last = self.read(env, len)?;
+
+        // Read the greater children (and their index) before writing anything.
+        let mut children = Vec::new(env);
+        let mut index = 0;
+        loop {
+            let mut child_index = 2 * index + 1;
+            if child_index >= len {
+                break;
+            }
+            let mut child = self.read(env, child_index)?;
+            if child_index + 1 < len {
+                let right = self.read(env, child_index + 1)?;
+                if right > child {
+                    child = right;
+                    child_index += 1;
+                }
+            }
+            if last >= child {
+                break;
+            }
+            children.push_back((child_index, child));
+            index = child_index;
+        }
+
+        // Move them up into the hole left at the root.
+        StorageProxy::new(&self.element_key(len)).remove(env);
+        self.set_len(env, len);
+        if len == 0 {
+            return Ok(Some(top));
+        }
+        let mut index = 0;
+        for position in 0..children.len() {
+            let (child_index, child) = children.get_unchecked(position);
+            self.write(env, index, &child);
+            index = child_index;
+        }
+        self.write(env, index, &last);
+        Ok(Some(top))
+    }
+
+    /// Get up to `limit` elements in storage (heap) order, starting from
+    /// index `start`.
+    pub fn page(&self, env: &Env, start: u32, limit: u32) -> Vec<T> {
+        let end = self.len(env).min(start.saturating_add(limit));
+        let mut page = Vec::new(env);
+        for index in start..end {
+            if let Some(data) = StorageProxy::new(&self.element_key(index)).get(env) {
+                page.push_back(data);
+            }
+        }
+        page
+    }
+
+    /// Remove all the elements.
+    pub fn clear(&self, env: &Env) {
+        for index in 0..self.len(env) {
+            StorageProxy::new(&self.element_key(index)).remove(env);
+        }
+        self.set_len(env, 0);
+    }
+}
@@ -0,0 +1,158 @@
+/*
+    Copyright (c) 2023-2024 Frederic Kyung-jin Rezeau (오경진 吳景振)
+
+    This file is part of soroban-kit.
+
+    Licensed under the MIT License, this software is provided "AS IS",
+    no liability assumed. For details, see the LICENSE file in the
+    root directory.
+
+    Author: Fred Kyung-jin Rezeau <fred@litemint.com>
+*/
+
+use soroban_sdk::{
+    symbol_short, xdr::ToXdr, BytesN, ConversionError, Env, IntoVal, Symbol, TryFromVal, Val,
+};
+
+use core::{fmt, marker::PhantomData};
+
+use super::{tier_extend_ttl, tier_get, tier_has, tier_remove, tier_set, StorageTier};
+
+/// Storage key derived from the SHA-256 hash of the XDR-encoded key `K`.
+///
+/// Keys embedding large or unbounded data (e.g. `Bytes`) are stored under a
+/// fixed size `BytesN<32>` ledger key. The preimage is not recoverable from
+/// the hash unless the key is created with `with_preimage`.
+pub struct HashedKey<K> {
+    hash: BytesN<32>,
+    preimage: Option<Val>,
+    _key: PhantomData<*const K>,
+}
+
+impl<K> HashedKey<K>
+where
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+{
+    pub fn new(env: &Env, key: &K) -> Self {
+        let val: Val = key.into_val(env);
+        Self::from_hash(env.crypto().sha256(&val.to_xdr(env)))
+    }
+
+    /// Derive the hashed key carrying its preimage, which the typed storage
+    /// stores in the tier of the data, extends and removes along with it.
+    /// Data moved between tiers (see `move_tier`) keeps its preimage only
+    /// if the key carries it.
+    pub fn with_preimage(env: &Env, key: &K) -> Self {
+        HashedKey {
+            preimage: Some(key.into_val(env)),
+            ..Self::new(env, key)
+        }
+    }
+
+    pub fn from_hash(hash: BytesN<32>) -> Self {
+        HashedKey {
+            hash,
+            preimage: None,
+            _key: PhantomData,
+        }
+    }
+
+    pub fn hash(&self) -> &BytesN<32> {
+        &self.hash
+    }
+
+    /// Get the stored preimage from any tier, None if it was not stored.
+    pub fn preimage(&self, env: &Env) -> Option<K> {
+        let preimage_key = self.preimage_key();
+        let val: Val = [
+            StorageTier::Persistent,
+            StorageTier::Temporary,
+            StorageTier::Instance,
+        ]
+        .into_iter()
+        .find_map(|tier| tier_get(env, tier, &preimage_key))?;
+        K::try_from_val(env, &val).ok()
+    }
+
+    /// Store the preimage carried by the key in `tier`.
+    pub fn store_preimage(&self, env: &Env, tier: StorageTier) {
+        let preimage_key = self.preimage_key();
+        if let Some(preimage) = self.preimage {
+            if !tier_has(env, tier, &preimage_key) {
+                tier_set(env, tier, &preimage_key, &preimage);
+            }
+        }
+    }
+
+    /// Extend the TTL of the preimage stored in `tier`, if any.
+    pub fn extend_preimage_ttl(
+        &self,
+        env: &Env,
+        tier: StorageTier,
+        threshold: u32,
+        extend_to: u32,
+    ) {
+        let preimage_key = self.preimage_key();
+        if tier_has(env, tier, &preimage_key) {
+            tier_extend_ttl(env, tier, &preimage_key, threshold, extend_to);
+        }
+    }
+
+    /// Remove the preimage stored in `tier`.
+    pub fn remove_preimage(&self, env: &Env, tier: StorageTier) {
+        tier_remove(env, tier, &self.preimage_key());
+    }
+
+    fn preimage_key(&self) -> (Symbol, BytesN<32>) {
+        (symbol_short!("preimage"), self.hash.clone())
+    }
+}
+
+impl<K> Clone for HashedKey<K> {
+    fn clone(&self) -> Self {
+        HashedKey {
+            hash: self.hash.clone(),
+            preimage: self.preimage,
+            _key: PhantomData,
+        }
+    }
+}
+
+impl<K> PartialEq for HashedKey<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash
+    }
+}
+
+impl<K> Eq for HashedKey<K> {}
+
+impl<K> fmt::Debug for HashedKey<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("HashedKey").field(&self.hash).finish()
+    }
+}
+
+impl<K> TryFromVal<Env, HashedKey<K>> for Val {
+    type Error = ConversionError;
+
+    fn try_from_val(env: &Env, key: &HashedKey<K>) -> Result<Val, ConversionError> {
+        Ok(key.hash.into_val(env))
+    }
+}
+
+impl<K> TryFromVal<Env, Val> for HashedKey<K> {
+    type Error = ConversionError;
+
+    fn try_from_val(env: &Env, val: &Val) -> Result<Self, ConversionError> {
+        Ok(HashedKey {
+            hash: BytesN::try_from_val(env, val)?,
+            preimage: None,
+            _key: PhantomData,
+        })
+    }
+}
+
+// Hashed keys share the registry of the key type.
+impl<K: super::KeyRegistry> super::KeyRegistry for HashedKey<K> {
+    const REGISTRY: &'static str = K::REGISTRY;
+}
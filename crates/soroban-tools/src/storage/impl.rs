@@ -18,10 +18,12 @@ pub use crate::mock_storage::{
     with_instance_storage, with_persistent_storage, with_temporary_storage,
 };
 
-use soroban_sdk::{Env, IntoVal, TryFromVal, Val};
+use soroban_sdk::{symbol_short, Env, IntoVal, String, Symbol, TryFromVal, Val, Vec};
 
 use core::marker::PhantomData;
 
+use crate::error::KitError;
+
 /// Execute the provided closure with instance storage.
 #[inline]
 #[cfg(not(feature = "mock-storage"))]
@@ -65,7 +67,7 @@ impl<'a, K, T> StorageProxy<'a, K, T>
 where
     K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
 {
-    fn new(key: &'a K) -> Self {
+    pub(crate) fn new(key: &'a K) -> Self {
         StorageProxy {
             key,
             _data: PhantomData,
@@ -77,19 +79,513 @@ where
     }
 }
 
+/// Storage tier backing a data type.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StorageTier {
+    Instance = 0,
+    Persistent = 1,
+    Temporary = 2,
+}
+
+/// TTL policy applied by the typed storage when data is accessed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TtlPolicy {
+    pub threshold: u32,
+    pub extend_to: u32,
+    pub extend_on_read: bool,
+    pub extend_on_write: bool,
+}
+
+impl TtlPolicy {
+    pub const fn new(threshold: u32, extend_to: u32) -> Self {
+        TtlPolicy {
+            threshold,
+            extend_to,
+            extend_on_read: false,
+            extend_on_write: false,
+        }
+    }
+
+    pub const fn extend_on_read(mut self) -> Self {
+        self.extend_on_read = true;
+        self
+    }
+
+    pub const fn extend_on_write(mut self) -> Self {
+        self.extend_on_write = true;
+        self
+    }
+}
+
+/// Logical expiry policy applied by the typed storage, independent of the
+/// entry TTL.
+///
+/// Expiring data is stored along with its expiry ledger (`after` ledgers
+/// from the write) and read as missing once the ledger sequence passes it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ExpiryPolicy {
+    pub after: u32,
+    pub remove_expired: bool,
+}
+
+impl ExpiryPolicy {
+    pub const fn new(after: u32) -> Self {
+        ExpiryPolicy {
+            after,
+            remove_expired: false,
+        }
+    }
+
+    /// Remove expired data when it is read.
+    pub const fn remove_expired(mut self) -> Self {
+        self.remove_expired = true;
+        self
+    }
+
+    /// Expiry ledger of data written at the current ledger.
+    pub fn expiry_ledger(&self, env: &Env) -> u32 {
+        env.ledger().sequence().saturating_add(self.after)
+    }
+}
+
+// Untyped access to a storage tier.
+pub fn tier_get<K, V>(env: &Env, tier: StorageTier, key: &K) -> Option<V>
+where
+    K: IntoVal<Env, Val>,
+    V: TryFromVal<Env, Val>,
+{
+    match tier {
+        StorageTier::Instance => with_instance_storage(env, |storage| storage.get(key)),
+        StorageTier::Persistent => with_persistent_storage(env, |storage| storage.get(key)),
+        StorageTier::Temporary => with_temporary_storage(env, |storage| storage.get(key)),
+    }
+}
+
+pub fn tier_has<K>(env: &Env, tier: StorageTier, key: &K) -> bool
+where
+    K: IntoVal<Env, Val>,
+{
+    match tier {
+        StorageTier::Instance => with_instance_storage(env, |storage| storage.has(key)),
+        StorageTier::Persistent => with_persistent_storage(env, |storage| storage.has(key)),
+        StorageTier::Temporary => with_temporary_storage(env, |storage| storage.has(key)),
+    }
+}
+
+pub fn tier_set<K, V>(env: &Env, tier: StorageTier, key: &K, data: &V)
+where
+    K: IntoVal<Env, Val>,
+    V: IntoVal<Env, Val>,
+{
+    match tier {
+        StorageTier::Instance => with_instance_storage(env, |storage| storage.set(key, data)),
+        StorageTier::Persistent => with_persistent_storage(env, |storage| storage.set(key, data)),
+        StorageTier::Temporary => with_temporary_storage(env, |storage| storage.set(key, data)),
+    }
+}
+
+pub fn tier_remove<K>(env: &Env, tier: StorageTier, key: &K)
+where
+    K: IntoVal<Env, Val>,
+{
+    match tier {
+        StorageTier::Instance => with_instance_storage(env, |storage| storage.remove(key)),
+        StorageTier::Persistent => with_persistent_storage(env, |storage| storage.remove(key)),
+        StorageTier::Temporary => with_temporary_storage(env, |storage| storage.remove(key)),
+    }
+}
+
+pub fn tier_extend_ttl<K>(
+    env: &Env,
+    tier: StorageTier,
+    key: &K,
+    threshold: u32,
+    extend_to: u32,
+) where
+    K: IntoVal<Env, Val>,
+{
+    match tier {
+        // Instance storage TTL is bound to the contract instance.
+        StorageTier::Instance => {
+            with_instance_storage(env, |storage| storage.extend_ttl(threshold, extend_to))
+        }
+        StorageTier::Persistent => {
+            with_persistent_storage(env, |storage| storage.extend_ttl(key, threshold, extend_to))
+        }
+        StorageTier::Temporary => {
+            with_temporary_storage(env, |storage| storage.extend_ttl(key, threshold, extend_to))
+        }
+    }
+}
+
+// Apply the TTL policy of the data type to an entry.
+pub fn tier_bump<K>(
+    env: &Env,
+    tier: StorageTier,
+    key: &K,
+    ttl: Option<TtlPolicy>,
+    write: bool,
+) where
+    K: IntoVal<Env, Val>,
+{
+    match ttl {
+        Some(ttl) if (write && ttl.extend_on_write) || (!write && ttl.extend_on_read) => {
+            tier_extend_ttl(env, tier, key, ttl.threshold, ttl.extend_to)
+        }
+        _ => {}
+    }
+}
+
+/// Schema version of a data type (see `impl_storage!` options).
+///
+/// Versioned data is stored along with its version tag and migrated
+/// from older versions when read.
+pub trait Versioned: Sized + IntoVal<Env, Val> + TryFromVal<Env, Val> {
+    /// Schema version, 0 for untagged data.
+    const VERSION: u32 = 0;
+
+    /// Decode data stored with `version` into the current schema.
+    fn migrate(env: &Env, version: u32, data: Val) -> Option<Self> {
+        if version == Self::VERSION {
+            Self::try_from_val(env, &data).ok()
+        } else {
+            None
+        }
+    }
+}
+
+/// Encode the data along with its version tag.
+///
+/// Versioned data is stored as `("v", version, data)` so that it is not
+/// mistaken for untagged data such as tuples or enums.
+pub fn to_versioned_val<T: Versioned>(env: &Env, data: &T) -> Val {
+    (symbol_short!("v"), T::VERSION, data.into_val(env)).into_val(env)
+}
+
+/// Decode versioned data, migrating it from older versions if needed.
+/// Returns the data and whether it was migrated.
+pub fn from_versioned_val<T: Versioned>(env: &Env, val: Val) -> (T, bool) {
+    // Data written before versioning was enabled is untagged.
+    let (version, data) = split_tag(env, &val, symbol_short!("v")).unwrap_or((0, val));
+    match T::migrate(env, version, data) {
+        Some(data) => (data, version != T::VERSION),
+        None => soroban_sdk::panic_with_error!(env, KitError::InvalidVersion),
+    }
+}
+
+/// Encode the data for the storage, along with its version tag and its
+/// expiry ledger if any.
+///
+/// Expiring data is stored as `("expires", expires_at, data)` so that it is
+/// not mistaken for untagged data such as versioned data or tuples.
+pub fn to_stored_val<T: Versioned>(env: &Env, data: &T, expires_at: Option<u32>) -> Val {
+    let data = if T::VERSION == 0 {
+        data.into_val(env)
+    } else {
+        to_versioned_val(env, data)
+    };
+    match expires_at {
+        Some(expires_at) => (symbol_short!("expires"), expires_at, data).into_val(env),
+        None => data,
+    }
+}
+
+// Unpack `(tag, value, data)` tagged data, None for untagged data (tuples
+// are unpacked by the host only once their length is known).
+fn split_tag(env: &Env, val: &Val, tag: Symbol) -> Option<(u32, Val)> {
+    Vec::<Val>::try_from_val(env, val)
+        .ok()
+        .filter(|tuple| tuple.len() == 3)
+        .and_then(|tuple| <(Symbol, u32, Val)>::try_from_val(env, &tuple.to_val()).ok())
+        .filter(|(symbol, _, _)| *symbol == tag)
+        .map(|(_, value, data)| (value, data))
+}
+
+/// Split the expiry ledger from stored expiring data.
+pub fn split_expiry(env: &Env, val: Val) -> (Option<u32>, Val) {
+    // Data written before expiry was enabled never expires.
+    match split_tag(env, &val, symbol_short!("expires")) {
+        Some((expires_at, data)) => (Some(expires_at), data),
+        None => (None, val),
+    }
+}
+
+/// Decode stored data, migrating it from older versions if needed.
+/// Returns the data, whether it was migrated and its expiry ledger.
+pub fn from_stored_val<T: Versioned>(
+    env: &Env,
+    val: Val,
+    expiring: bool,
+) -> (T, bool, Option<u32>) {
+    let (expires_at, data) = if expiring {
+        split_expiry(env, val)
+    } else {
+        (None, val)
+    };
+    if T::VERSION == 0 {
+        (T::try_from_val(env, &data).unwrap(), false, expires_at)
+    } else {
+        let (data, migrated) = from_versioned_val(env, data);
+        (data, migrated, expires_at)
+    }
+}
+
+/// Whether data with the expiry ledger `expires_at` has expired.
+pub fn is_expired(env: &Env, expires_at: Option<u32>) -> bool {
+    expires_at.is_some_and(|expires_at| env.ledger().sequence() > expires_at)
+}
+
+/// Secondary indexes of a data type (see `impl_storage!` options).
+///
+/// Index entries map each indexed field value to the keys of the data.
+pub trait Indexed: Sized {
+    /// Index namespace, empty if the data type has no secondary index.
+    const INDEX: &'static str = "";
+
+    /// Update the index entries of `key` when its data changes.
+    fn reindex(
+        _env: &Env,
+        _tier: StorageTier,
+        _ttl: Option<TtlPolicy>,
+        _key: Val,
+        _previous: Option<&Self>,
+        _data: Option<&Self>,
+    ) {
+    }
+}
+
+fn index_key<T: Indexed>(env: &Env, field: &str, value: Val) -> (String, Symbol, Val) {
+    (String::from_str(env, T::INDEX), Symbol::new(env, field), value)
+}
+
+/// Insert or remove `key` from the index entry of the field `value`.
+pub fn update_index<T, V>(
+    env: &Env,
+    tier: StorageTier,
+    ttl: Option<TtlPolicy>,
+    field: &str,
+    value: &V,
+    key: Val,
+    insert: bool,
+) where
+    T: Indexed,
+    V: IntoVal<Env, Val>,
+{
+    let index_key = index_key::<T>(env, field, value.into_val(env));
+    let mut keys: Vec<Val> = tier_get(env, tier, &index_key).unwrap_or_else(|| Vec::new(env));
+    match (keys.first_index_of(key), insert) {
+        (None, true) => keys.push_back(key),
+        (Some(index), false) => {
+            keys.remove(index);
+        }
+        _ => return,
+    }
+    if keys.is_empty() {
+        tier_remove(env, tier, &index_key);
+    } else {
+        tier_set(env, tier, &index_key, &keys);
+        tier_bump(env, tier, &index_key, ttl, true);
+    }
+}
+
+/// Find the keys of the data whose indexed `field` equals `value`.
+pub fn find_by_index<T, K, V>(env: &Env, field: &str, value: &V) -> Vec<K>
+where
+    T: Indexed,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+    V: IntoVal<Env, Val>,
+    for<'a> StorageProxy<'a, K, T>: StorageOps<T>,
+{
+    let tier = <StorageProxy<K, T> as StorageOps<T>>::TIER;
+    let index_key = index_key::<T>(env, field, value.into_val(env));
+    let keys: Vec<Val> = tier_get(env, tier, &index_key).unwrap_or_else(|| Vec::new(env));
+    let mut result = Vec::new(env);
+    for key in keys.iter() {
+        if let Ok(key) = K::try_from_val(env, &key) {
+            result.push_back(key);
+        }
+    }
+    result
+}
+
+/// Publish a storage event for the `operation` on the data type entry
+/// stored under `key`.
+///
+/// Events are published with the topics `("storage", operation, data_type)`
+/// and the key as data.
+pub fn publish_event(env: &Env, operation: &str, data_type: &str, key: Val) {
+    env.events().publish(
+        (
+            symbol_short!("storage"),
+            Symbol::new(env, operation),
+            String::from_str(env, data_type),
+        ),
+        key,
+    );
+}
+
 /// Trait for storage operations.
 pub trait StorageOps<T> {
-    fn get(&self, env: &Env) -> Option<T>;
-    fn set(&self, env: &Env, data: &T);
-    fn remove(&self, env: &Env);
-    fn has(&self, env: &Env) -> bool;
-    fn extend_ttl(&self, env: &Env, threshold: u32, extend_to: u32);
+    /// Storage tier for the data type.
+    const TIER: StorageTier;
+
+    /// TTL policy for the data type (see `impl_storage!` options).
+    const TTL: Option<TtlPolicy> = None;
+
+    /// Data type name published with storage events, None if disabled
+    /// (see `impl_storage!` options).
+    const EVENTS: Option<&'static str> = None;
+
+    /// Logical expiry policy for the data type (see `impl_storage!` options).
+    const EXPIRY: Option<ExpiryPolicy> = None;
+
+    /// Registry recording the keys holding data, None if the key type is
+    /// not registered (see `impl_key_constraint!`).
+    const REGISTRY: Option<&'static str> = None;
+
+    /// Get the data stored in `tier` rather than in the data type tier.
+    fn get_in(&self, env: &Env, tier: StorageTier) -> Option<T>;
+    /// Set the data in `tier` rather than in the data type tier.
+    fn set_in(&self, env: &Env, tier: StorageTier, data: &T);
+    /// Remove the data from `tier` rather than from the data type tier.
+    fn remove_in(&self, env: &Env, tier: StorageTier);
+    /// Whether `tier` rather than the data type tier holds the data.
+    fn has_in(&self, env: &Env, tier: StorageTier) -> bool;
+    /// Extend the data TTL in `tier` rather than in the data type tier.
+    fn extend_ttl_in(&self, env: &Env, tier: StorageTier, threshold: u32, extend_to: u32);
+
+    fn get(&self, env: &Env) -> Option<T> {
+        self.get_in(env, Self::TIER)
+    }
+
+    fn set(&self, env: &Env, data: &T) {
+        self.set_in(env, Self::TIER, data)
+    }
+
+    fn remove(&self, env: &Env) {
+        self.remove_in(env, Self::TIER)
+    }
+
+    fn has(&self, env: &Env) -> bool {
+        self.has_in(env, Self::TIER)
+    }
+
+    fn extend_ttl(&self, env: &Env, threshold: u32, extend_to: u32) {
+        self.extend_ttl_in(env, Self::TIER, threshold, extend_to)
+    }
+
+    /// Get the data or `KitError::NotFound` if missing.
+    fn try_get(&self, env: &Env) -> Result<T, KitError> {
+        self.get(env).ok_or(KitError::NotFound)
+    }
+
+    fn try_set(&self, env: &Env, data: &T) -> Result<(), KitError> {
+        self.set(env, data);
+        Ok(())
+    }
+
+    /// Remove the data or `KitError::NotFound` if missing.
+    fn try_remove(&self, env: &Env) -> Result<(), KitError> {
+        if !self.has(env) {
+            return Err(KitError::NotFound);
+        }
+        self.remove(env);
+        Ok(())
+    }
+
+    /// Extend the data TTL or `KitError::NotFound` if missing
+    /// (the host traps when extending a missing entry).
+    fn try_extend_ttl(&self, env: &Env, threshold: u32, extend_to: u32) -> Result<(), KitError> {
+        if !self.has(env) {
+            return Err(KitError::NotFound);
+        }
+        self.extend_ttl(env, threshold, extend_to);
+        Ok(())
+    }
+
+    /// Set the data computed from the current data and return it.
+    fn update<F>(&self, env: &Env, f: F) -> T
+    where
+        F: FnOnce(Option<T>) -> T,
+    {
+        let data = f(self.get(env));
+        self.set(env, &data);
+        data
+    }
+
+    /// Get the data, initializing it first if missing.
+    fn get_or_init<F>(&self, env: &Env, init: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        self.get(env).unwrap_or_else(|| {
+            let data = init();
+            self.set(env, &data);
+            data
+        })
+    }
+
+    /// Remove the data and return it.
+    fn take(&self, env: &Env) -> Option<T> {
+        let data = self.get(env);
+        if data.is_some() {
+            self.remove(env);
+        }
+        data
+    }
+
+    /// Set the data and return the previous data.
+    fn replace(&self, env: &Env, data: &T) -> Option<T> {
+        let previous = self.get(env);
+        self.set(env, data);
+        previous
+    }
+
+    /// Add `delta` to the counter (starting from zero if missing)
+    /// or `KitError::Overflow`.
+    fn increment(&self, env: &Env, delta: T::Delta) -> Result<T, KitError>
+    where
+        T: Counter,
+    {
+        let data = self
+            .get(env)
+            .unwrap_or_else(T::zero)
+            .checked_add(delta)
+            .ok_or(KitError::Overflow)?;
+        self.set(env, &data);
+        Ok(data)
+    }
+
+    /// Subtract `delta` from the counter (starting from zero if missing)
+    /// or `KitError::Underflow`.
+    fn decrement(&self, env: &Env, delta: T::Delta) -> Result<T, KitError>
+    where
+        T: Counter,
+    {
+        let data = self
+            .get(env)
+            .unwrap_or_else(T::zero)
+            .checked_sub(delta)
+            .ok_or(KitError::Underflow)?;
+        self.set(env, &data);
+        Ok(data)
+    }
+}
+
+/// Numeric data types supporting checked `increment` and `decrement`
+/// (see `impl_counter!`).
+pub trait Counter: Sized {
+    type Delta;
+
+    fn zero() -> Self;
+    fn checked_add(&self, delta: Self::Delta) -> Option<Self>;
+    fn checked_sub(&self, delta: Self::Delta) -> Option<Self>;
 }
 
 pub fn get<'a, K, T>(env: &Env, key: &'a K) -> Option<T>
 where
     StorageProxy<'a, K, T>: StorageOps<T>,
-    K: IntoVal<Env, Val> + TryFromVal<Env, Val> + ?Sized,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
 {
     StorageProxy::<'a, K, T>::new(key).get(env)
 }
@@ -97,7 +593,7 @@ where
 pub fn get_or_else<'a, K, T, F, R>(env: &Env, key: &'a K, handler: F) -> R
 where
     StorageProxy<'a, K, T>: StorageOps<T>,
-    K: IntoVal<Env, Val> + TryFromVal<Env, Val> + ?Sized,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
     F: FnOnce(Option<T>) -> R,
 {
     handler(StorageProxy::<'a, K, T>::new(key).get(env))
@@ -106,7 +602,7 @@ where
 pub fn set<'a, K, T>(env: &Env, key: &'a K, data: &T)
 where
     StorageProxy<'a, K, T>: StorageOps<T>,
-    K: IntoVal<Env, Val> + TryFromVal<Env, Val> + ?Sized,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
 {
     StorageProxy::<'a, K, T>::new(key).set(env, data);
 }
@@ -114,7 +610,7 @@ where
 pub fn has<'a, K, T>(env: &Env, key: &'a K) -> bool
 where
     StorageProxy<'a, K, T>: StorageOps<T>,
-    K: IntoVal<Env, Val> + TryFromVal<Env, Val> + ?Sized,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
 {
     StorageProxy::<'a, K, T>::new(key).has(env)
 }
@@ -122,7 +618,7 @@ where
 pub fn remove<'a, K, T>(env: &Env, key: &'a K)
 where
     StorageProxy<'a, K, T>: StorageOps<T>,
-    K: IntoVal<Env, Val> + TryFromVal<Env, Val> + ?Sized,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
 {
     StorageProxy::<'a, K, T>::new(key).remove(env);
 }
@@ -134,132 +630,600 @@ pub fn extend_ttl<'a, K, T>(
     extend_to: u32,
 ) where
     StorageProxy<'a, K, T>: StorageOps<T>,
-    K: IntoVal<Env, Val> + TryFromVal<Env, Val> + ?Sized,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
 {
     StorageProxy::<'a, K, T>::new(key).extend_ttl(env, threshold, extend_to);
 }
 
+/// Get the data stored in `tier` rather than in the data type tier.
+pub fn get_in<'a, K, T>(env: &Env, tier: StorageTier, key: &'a K) -> Option<T>
+where
+    StorageProxy<'a, K, T>: StorageOps<T>,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+{
+    StorageProxy::<'a, K, T>::new(key).get_in(env, tier)
+}
+
+/// Set the data in `tier` rather than in the data type tier.
+pub fn set_in<'a, K, T>(env: &Env, tier: StorageTier, key: &'a K, data: &T)
+where
+    StorageProxy<'a, K, T>: StorageOps<T>,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+{
+    StorageProxy::<'a, K, T>::new(key).set_in(env, tier, data);
+}
+
+/// Whether `tier` rather than the data type tier holds the data.
+pub fn has_in<'a, K, T>(env: &Env, tier: StorageTier, key: &'a K) -> bool
+where
+    StorageProxy<'a, K, T>: StorageOps<T>,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+{
+    StorageProxy::<'a, K, T>::new(key).has_in(env, tier)
+}
+
+/// Remove the data from `tier` rather than from the data type tier.
+pub fn remove_in<'a, K, T>(env: &Env, tier: StorageTier, key: &'a K)
+where
+    StorageProxy<'a, K, T>: StorageOps<T>,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+{
+    StorageProxy::<'a, K, T>::new(key).remove_in(env, tier);
+}
+
+/// Extend the data TTL in `tier` rather than in the data type tier.
+pub fn extend_ttl_in<'a, K, T>(
+    env: &Env,
+    tier: StorageTier,
+    key: &'a K,
+    threshold: u32,
+    extend_to: u32,
+) where
+    StorageProxy<'a, K, T>: StorageOps<T>,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+{
+    StorageProxy::<'a, K, T>::new(key).extend_ttl_in(env, tier, threshold, extend_to);
+}
+
+pub fn try_get<'a, K, T>(env: &Env, key: &'a K) -> Result<T, KitError>
+where
+    StorageProxy<'a, K, T>: StorageOps<T>,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+{
+    StorageProxy::<'a, K, T>::new(key).try_get(env)
+}
+
+pub fn try_set<'a, K, T>(env: &Env, key: &'a K, data: &T) -> Result<(), KitError>
+where
+    StorageProxy<'a, K, T>: StorageOps<T>,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+{
+    StorageProxy::<'a, K, T>::new(key).try_set(env, data)
+}
+
+pub fn try_remove<'a, K, T>(env: &Env, key: &'a K) -> Result<(), KitError>
+where
+    StorageProxy<'a, K, T>: StorageOps<T>,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+{
+    StorageProxy::<'a, K, T>::new(key).try_remove(env)
+}
+
+pub fn try_extend_ttl<'a, K, T>(
+    env: &Env,
+    key: &'a K,
+    threshold: u32,
+    extend_to: u32,
+) -> Result<(), KitError>
+where
+    StorageProxy<'a, K, T>: StorageOps<T>,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+{
+    StorageProxy::<'a, K, T>::new(key).try_extend_ttl(env, threshold, extend_to)
+}
+
+pub fn update<'a, K, T, F>(env: &Env, key: &'a K, f: F) -> T
+where
+    StorageProxy<'a, K, T>: StorageOps<T>,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+    F: FnOnce(Option<T>) -> T,
+{
+    StorageProxy::<'a, K, T>::new(key).update(env, f)
+}
+
+pub fn get_or_init<'a, K, T, F>(env: &Env, key: &'a K, init: F) -> T
+where
+    StorageProxy<'a, K, T>: StorageOps<T>,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+    F: FnOnce() -> T,
+{
+    StorageProxy::<'a, K, T>::new(key).get_or_init(env, init)
+}
+
+pub fn take<'a, K, T>(env: &Env, key: &'a K) -> Option<T>
+where
+    StorageProxy<'a, K, T>: StorageOps<T>,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+{
+    StorageProxy::<'a, K, T>::new(key).take(env)
+}
+
+pub fn replace<'a, K, T>(env: &Env, key: &'a K, data: &T) -> Option<T>
+where
+    StorageProxy<'a, K, T>: StorageOps<T>,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+{
+    StorageProxy::<'a, K, T>::new(key).replace(env, data)
+}
+
+pub fn increment<'a, K, T>(env: &Env, key: &'a K, delta: T::Delta) -> Result<T, KitError>
+where
+    StorageProxy<'a, K, T>: StorageOps<T>,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+    T: Counter,
+{
+    StorageProxy::<'a, K, T>::new(key).increment(env, delta)
+}
+
+pub fn decrement<'a, K, T>(env: &Env, key: &'a K, delta: T::Delta) -> Result<T, KitError>
+where
+    StorageProxy<'a, K, T>: StorageOps<T>,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+    T: Counter,
+{
+    StorageProxy::<'a, K, T>::new(key).decrement(env, delta)
+}
+
+#[macro_export]
+macro_rules! impl_counter {
+    ($counter_type:ident, $value_type:ty) => {
+        impl $crate::storage::Counter for $counter_type {
+            type Delta = $value_type;
+
+            fn zero() -> Self {
+                $counter_type(0)
+            }
+
+            fn checked_add(&self, delta: $value_type) -> Option<Self> {
+                self.0.checked_add(delta).map($counter_type)
+            }
+
+            fn checked_sub(&self, delta: $value_type) -> Option<Self> {
+                self.0.checked_sub(delta).map($counter_type)
+            }
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! impl_key_constraint {
+    // Generic key types: `[T: Bound] Key<T>, KeyTrait where [T: Bound]`.
+    ([$($generics:tt)*] $key_type:ty, $key_trait:ident $(where [$($pred:tt)*])?) => {
+        $crate::impl_key_constraint!(@trait $key_trait);
+        impl<$($generics)*> $key_trait for $key_type where $($($pred)*)? {}
+    };
+    // Keys holding data are recorded in a registry (see `storage::keys`).
+    ($key_type:ty, $key_trait:ident; registry) => {
+        $crate::impl_key_constraint!(@trait $key_trait);
+        impl $key_trait for $key_type {
+            const REGISTRY: Option<&'static str> = Some(stringify!($key_type));
+        }
+        impl $crate::storage::KeyRegistry for $key_type {
+            const REGISTRY: &'static str = stringify!($key_type);
+        }
+    };
+    // Keys are only accepted hashed (see `storage::HashedKey`).
+    ($key_type:ty, $key_trait:ident; hashed) => {
+        $crate::impl_key_constraint!(@trait $key_trait);
+        impl $key_trait for $crate::storage::HashedKey<$key_type> {
+            $crate::impl_key_constraint!(@preimage);
+        }
+    };
+    ($key_type:ty, $key_trait:ident; hashed, registry) => {
+        $crate::impl_key_constraint!(@trait $key_trait);
+        impl $key_trait for $crate::storage::HashedKey<$key_type> {
+            const REGISTRY: Option<&'static str> = Some(stringify!($key_type));
+            $crate::impl_key_constraint!(@preimage);
+        }
+        impl $crate::storage::KeyRegistry for $key_type {
+            const REGISTRY: &'static str = stringify!($key_type);
+        }
+    };
     ($key_type:ty, $key_trait:ident) => {
-        pub trait $key_trait {}
+        $crate::impl_key_constraint!(@trait $key_trait);
         impl $key_trait for $key_type {}
     };
-}
+    (@trait $key_trait:ident) => {
+        pub trait $key_trait {
+            /// Key registry name, None if the keys are not registered.
+            const REGISTRY: Option<&'static str> = None;
 
-#[macro_export]
-macro_rules! impl_storage {
-    (Instance, $data_type:ty $(, $key_trait:ident)?) => {
-        impl<'a, K> $crate::storage::StorageOps<$data_type>
-            for $crate::storage::StorageProxy<'a, K, $data_type>
-        where
-            K: $( $key_trait + )? soroban_sdk::IntoVal<soroban_sdk::Env, soroban_sdk::Val>
-                + soroban_sdk::TryFromVal<soroban_sdk::Env, soroban_sdk::Val>,
-        {
-            fn get(&self, env: &soroban_sdk::Env) -> Option<$data_type> {
-                $crate::storage::with_instance_storage(env, |storage| storage.get(self.get_key()))
+            /// Store the key preimage along with the data (hashed keys only).
+            fn store_key_preimage(
+                &self,
+                _env: &soroban_sdk::Env,
+                _tier: $crate::storage::StorageTier,
+            ) {
             }
 
-            fn set(&self, env: &soroban_sdk::Env, data: &$data_type) {
-                $crate::storage::with_instance_storage(env, |storage| {
-                    storage.set(self.get_key(), data)
-                });
+            /// Extend the key preimage TTL along with the data (hashed keys only).
+            fn extend_key_preimage_ttl(
+                &self,
+                _env: &soroban_sdk::Env,
+                _tier: $crate::storage::StorageTier,
+                _threshold: u32,
+                _extend_to: u32,
+            ) {
             }
 
-            fn remove(&self, env: &soroban_sdk::Env) {
-                $crate::storage::with_instance_storage(env, |storage| {
-                    storage.remove(self.get_key())
-                });
+            /// Remove the key preimage along with the data (hashed keys only).
+            fn remove_key_preimage(
+                &self,
+                _env: &soroban_sdk::Env,
+                _tier: $crate::storage::StorageTier,
+            ) {
             }
+        }
+    };
+    (@preimage) => {
+        fn store_key_preimage(
+            &self,
+            env: &soroban_sdk::Env,
+            tier: $crate::storage::StorageTier,
+        ) {
+            self.store_preimage(env, tier);
+        }
 
-            fn has(&self, env: &soroban_sdk::Env) -> bool {
-                $crate::storage::with_instance_storage(env, |storage| storage.has(self.get_key()))
-            }
+        fn extend_key_preimage_ttl(
+            &self,
+            env: &soroban_sdk::Env,
+            tier: $crate::storage::StorageTier,
+            threshold: u32,
+            extend_to: u32,
+        ) {
+            self.extend_preimage_ttl(env, tier, threshold, extend_to);
+        }
 
-            fn extend_ttl(&self, env: &Env, threshold: u32, extend_to: u32) {
-                $crate::storage::with_instance_storage(env, |storage| {
-                    storage.extend_ttl(
-                        threshold,
-                        extend_to,
-                    )
-                });
-            }
+        fn remove_key_preimage(
+            &self,
+            env: &soroban_sdk::Env,
+            tier: $crate::storage::StorageTier,
+        ) {
+            self.remove_preimage(env, tier);
         }
     };
-    (Persistent, $data_type:ty $(, $key_type:ident)?) => {
-        impl<'a, K> $crate::storage::StorageOps<$data_type>
+}
+
+#[macro_export]
+macro_rules! impl_storage {
+    ($([$($generics:tt)*])? Instance, $data_type:ty $(, $key_trait:ident)?
+        $(where [$($pred:tt)*])? $(; $($option:tt)*)?) => {
+        $crate::impl_storage!(@internal Instance, $data_type,
+            [$($($generics)*)?], [$($($pred)*)?], ($($key_trait)?), ($($($option)*)?));
+    };
+    ($([$($generics:tt)*])? Persistent, $data_type:ty $(, $key_trait:ident)?
+        $(where [$($pred:tt)*])? $(; $($option:tt)*)?) => {
+        $crate::impl_storage!(@internal Persistent, $data_type,
+            [$($($generics)*)?], [$($($pred)*)?], ($($key_trait)?), ($($($option)*)?));
+    };
+    ($([$($generics:tt)*])? Temporary, $data_type:ty $(, $key_trait:ident)?
+        $(where [$($pred:tt)*])? $(; $($option:tt)*)?) => {
+        $crate::impl_storage!(@internal Temporary, $data_type,
+            [$($($generics)*)?], [$($($pred)*)?], ($($key_trait)?), ($($($option)*)?));
+    };
+    // @internal
+    (@internal $storage_type:ident, $data_type:ty,
+        [$($generics:tt)*], [$($pred:tt)*], ($($key_trait:ident)?), ($($option:tt)*)) => {
+        impl<'a, K, $($generics)*> $crate::storage::StorageOps<$data_type>
             for $crate::storage::StorageProxy<'a, K, $data_type>
         where
-            K: $( $key_type + )? soroban_sdk::IntoVal<soroban_sdk::Env, soroban_sdk::Val>
+            K: $( $key_trait + )? soroban_sdk::IntoVal<soroban_sdk::Env, soroban_sdk::Val>
+                + soroban_sdk::TryFromVal<soroban_sdk::Env, soroban_sdk::Val>,
+            $data_type: soroban_sdk::IntoVal<soroban_sdk::Env, soroban_sdk::Val>
                 + soroban_sdk::TryFromVal<soroban_sdk::Env, soroban_sdk::Val>,
+            $($pred)*
         {
-            fn get(&self, env: &soroban_sdk::Env) -> Option<$data_type> {
-                $crate::storage::with_persistent_storage(env, |storage| storage.get(self.get_key()))
+            const TIER: $crate::storage::StorageTier = $crate::storage::StorageTier::$storage_type;
+
+            $crate::impl_storage!(@options $data_type; $($option)*);
+            $($crate::impl_storage!(@registry $key_trait);)?
+
+            fn get_in(
+                &self,
+                env: &soroban_sdk::Env,
+                tier: $crate::storage::StorageTier,
+            ) -> Option<$data_type> {
+                let data = if <$data_type as $crate::storage::Versioned>::VERSION == 0
+                    && Self::EXPIRY.is_none()
+                {
+                    $crate::storage::tier_get(env, tier, self.get_key())
+                } else {
+                    $crate::storage::tier_get::<_, soroban_sdk::Val>(env, tier, self.get_key())
+                    .and_then(|val| {
+                        let (data, migrated, expires_at) = $crate::storage::from_stored_val(
+                            env, val, Self::EXPIRY.is_some());
+                        if $crate::storage::is_expired(env, expires_at) {
+                            if Self::EXPIRY.is_some_and(|expiry| expiry.remove_expired) {
+                                self.remove_in(env, tier);
+                            }
+                            return None;
+                        }
+                        // Migrated data is written back with the current version
+                        // (expiring data keeps its expiry ledger).
+                        if migrated && expires_at.is_some() {
+                            $crate::storage::tier_set(env, tier, self.get_key(),
+                                &$crate::storage::to_stored_val(env, &data, expires_at));
+                        } else if migrated {
+                            self.set_in(env, tier, &data);
+                        }
+                        Some(data)
+                    })
+                };
+                match Self::TTL {
+                    Some(ttl) if ttl.extend_on_read && data.is_some() => {
+                        self.extend_ttl_in(env, tier, ttl.threshold, ttl.extend_to)
+                    }
+                    _ => {}
+                }
+                data
             }
 
-            fn set(&self, env: &soroban_sdk::Env, data: &$data_type) {
-                $crate::storage::with_persistent_storage(env, |storage| {
-                    storage.set(self.get_key(), data)
-                });
+            fn set_in(
+                &self,
+                env: &soroban_sdk::Env,
+                tier: $crate::storage::StorageTier,
+                data: &$data_type,
+            ) {
+                let indexed = !<$data_type as $crate::storage::Indexed>::INDEX.is_empty();
+                let previous = if indexed {
+                    $crate::impl_storage!(@read env, tier, self.get_key(), $data_type)
+                } else {
+                    None
+                };
+                if <$data_type as $crate::storage::Versioned>::VERSION == 0
+                    && Self::EXPIRY.is_none()
+                {
+                    $crate::storage::tier_set(env, tier, self.get_key(), data)
+                } else {
+                    let expires_at = Self::EXPIRY.map(|expiry| expiry.expiry_ledger(env));
+                    $crate::storage::tier_set(env, tier, self.get_key(),
+                        &$crate::storage::to_stored_val(env, data, expires_at))
+                }
+                // Registered first so the TTL policy also extends the registry entry
+                // and the key preimage.
+                $crate::impl_storage!(@register env, self, tier, true);
+                $(<K as $key_trait>::store_key_preimage(self.get_key(), env, tier);)?
+                match Self::TTL {
+                    Some(ttl) if ttl.extend_on_write => {
+                        self.extend_ttl_in(env, tier, ttl.threshold, ttl.extend_to)
+                    }
+                    _ => {}
+                }
+                if indexed {
+                    <$data_type as $crate::storage::Indexed>::reindex(env, tier, Self::TTL,
+                        soroban_sdk::IntoVal::into_val(self.get_key(), env), previous.as_ref(),
+                        Some(data));
+                }
+                $crate::impl_storage!(@event env, self, "set");
             }
 
-            fn remove(&self, env: &soroban_sdk::Env) {
-                $crate::storage::with_persistent_storage(env, |storage| {
-                    storage.remove(self.get_key())
-                });
+            fn remove_in(&self, env: &soroban_sdk::Env, tier: $crate::storage::StorageTier) {
+                let indexed = !<$data_type as $crate::storage::Indexed>::INDEX.is_empty();
+                let previous = if indexed {
+                    $crate::impl_storage!(@read env, tier, self.get_key(), $data_type)
+                } else {
+                    None
+                };
+                $crate::storage::tier_remove(env, tier, self.get_key());
+                $(<K as $key_trait>::remove_key_preimage(self.get_key(), env, tier);)?
+                if indexed {
+                    <$data_type as $crate::storage::Indexed>::reindex(env, tier, Self::TTL,
+                        soroban_sdk::IntoVal::into_val(self.get_key(), env), previous.as_ref(),
+                        None);
+                }
+                $crate::impl_storage!(@event env, self, "remove");
+                $crate::impl_storage!(@register env, self, tier, false);
             }
 
-            fn has(&self, env: &soroban_sdk::Env) -> bool {
-                $crate::storage::with_persistent_storage(env, |storage| storage.has(self.get_key()))
+            fn has_in(&self, env: &soroban_sdk::Env, tier: $crate::storage::StorageTier) -> bool {
+                if Self::EXPIRY.is_none() {
+                    $crate::storage::tier_has(env, tier, self.get_key())
+                } else {
+                    $crate::storage::tier_get::<_, soroban_sdk::Val>(env, tier, self.get_key())
+                    .is_some_and(|val| {
+                        !$crate::storage::is_expired(env, $crate::storage::split_expiry(env, val).0)
+                    })
+                }
             }
 
-            fn extend_ttl(&self, env: &Env, threshold: u32, extend_to: u32) {
-                $crate::storage::with_persistent_storage(env, |storage| {
-                    storage.extend_ttl(
-                        self.get_key(),
-                        threshold,
-                        extend_to,
-                    )
-                });
+            fn extend_ttl_in(
+                &self,
+                env: &soroban_sdk::Env,
+                tier: $crate::storage::StorageTier,
+                threshold: u32,
+                extend_to: u32,
+            ) {
+                $crate::storage::tier_extend_ttl(env, tier, self.get_key(), threshold, extend_to);
+                $(<K as $key_trait>::extend_key_preimage_ttl(self.get_key(), env, tier, threshold,
+                    extend_to);)?
+                if let Some(registry) = Self::REGISTRY {
+                    $crate::storage::extend_registered_key(env, registry,
+                        soroban_sdk::IntoVal::into_val(self.get_key(), env), threshold, extend_to);
+                }
+                $crate::impl_storage!(@event env, self, "extend_ttl");
             }
         }
+
+        $crate::impl_storage!(@versioned [$($generics)*] [$($pred)*] $data_type; $($option)*);
+        $crate::impl_storage!(@indexed [$($generics)*] [$($pred)*] $data_type; $($option)*);
     };
-    (Temporary, $data_type:ty $(, $key_type:ident)?) => {
-        impl<'a, K> $crate::storage::StorageOps<$data_type>
-            for $crate::storage::StorageProxy<'a, K, $data_type>
+    (@registry $key_trait:ident) => {
+        const REGISTRY: Option<&'static str> = <K as $key_trait>::REGISTRY;
+    };
+    (@register $env:ident, $proxy:ident, $tier:ident, $insert:literal) => {
+        if let Some(registry) = Self::REGISTRY {
+            $crate::storage::register_key($env, registry, $tier,
+                soroban_sdk::IntoVal::into_val($proxy.get_key(), $env), $insert);
+        }
+    };
+    (@event $env:ident, $proxy:ident, $operation:literal) => {
+        if let Some(data_type) = Self::EVENTS {
+            $crate::storage::publish_event($env, $operation, data_type,
+                soroban_sdk::IntoVal::into_val($proxy.get_key(), $env));
+        }
+    };
+    // Read the data without side effects (TTL extension, migration write-back).
+    (@read $env:expr, $tier:expr, $key:expr, $data_type:ty) => {
+        if <$data_type as $crate::storage::Versioned>::VERSION == 0 && Self::EXPIRY.is_none() {
+            $crate::storage::tier_get::<_, $data_type>($env, $tier, $key)
+        } else {
+            $crate::storage::tier_get::<_, soroban_sdk::Val>($env, $tier, $key)
+            .map(|val| {
+                $crate::storage::from_stored_val::<$data_type>($env, val, Self::EXPIRY.is_some()).0
+            })
+        }
+    };
+    // @options
+    (@options $data_type:ty;) => {};
+    (@options $data_type:ty; ttl(threshold = $threshold:expr, extend_to = $extend_to:expr)
+        $(, $($rest:tt)*)?) => {
+        $crate::impl_storage!(@options $data_type; ttl(threshold = $threshold,
+            extend_to = $extend_to, extend_on = read | write) $(, $($rest)*)?);
+    };
+    (@options $data_type:ty; ttl(threshold = $threshold:expr, extend_to = $extend_to:expr,
+        extend_on = $($event:ident)|+) $(, $($rest:tt)*)?) => {
+        const TTL: Option<$crate::storage::TtlPolicy> = Some($crate::impl_storage!(@extend_on
+            $crate::storage::TtlPolicy::new($threshold, $extend_to); $($event)|+));
+        $crate::impl_storage!(@options $data_type; $($($rest)*)?);
+    };
+    (@options $data_type:ty; expiry(after = $after:expr) $(, $($rest:tt)*)?) => {
+        const EXPIRY: Option<$crate::storage::ExpiryPolicy> =
+            Some($crate::storage::ExpiryPolicy::new($after));
+        $crate::impl_storage!(@options $data_type; $($($rest)*)?);
+    };
+    (@options $data_type:ty; expiry(after = $after:expr, remove_expired) $(, $($rest:tt)*)?) => {
+        const EXPIRY: Option<$crate::storage::ExpiryPolicy> =
+            Some($crate::storage::ExpiryPolicy::new($after).remove_expired());
+        $crate::impl_storage!(@options $data_type; $($($rest)*)?);
+    };
+    (@options $data_type:ty; events $(, $($rest:tt)*)?) => {
+        const EVENTS: Option<&'static str> = Some(stringify!($data_type));
+        $crate::impl_storage!(@options $data_type; $($($rest)*)?);
+    };
+    (@options $data_type:ty; index($($field:ident),+) $(, $($rest:tt)*)?) => {
+        $crate::impl_storage!(@options $data_type; $($($rest)*)?);
+    };
+    (@options $data_type:ty; version = $version:literal, migrate_from = $from:ty
+        $(, $($rest:tt)*)?) => {
+        $crate::impl_storage!(@options $data_type; $($($rest)*)?);
+    };
+    (@options $data_type:ty; version = $version:literal $(, $($rest:tt)*)?) => {
+        $crate::impl_storage!(@options $data_type; $($($rest)*)?);
+    };
+    (@extend_on $policy:expr; read $(| $($event:ident)|+)?) => {
+        $crate::impl_storage!(@extend_on $policy.extend_on_read(); $($($event)|+)?)
+    };
+    (@extend_on $policy:expr; write $(| $($event:ident)|+)?) => {
+        $crate::impl_storage!(@extend_on $policy.extend_on_write(); $($($event)|+)?)
+    };
+    (@extend_on $policy:expr;) => {
+        $policy
+    };
+    // @versioned
+    (@versioned [$($generics:tt)*] [$($pred:tt)*] $data_type:ty;) => {
+        impl<$($generics)*> $crate::storage::Versioned for $data_type
+        where
+            $data_type: soroban_sdk::IntoVal<soroban_sdk::Env, soroban_sdk::Val>
+                + soroban_sdk::TryFromVal<soroban_sdk::Env, soroban_sdk::Val>,
+            $($pred)*
+        {}
+    };
+    (@versioned [$($generics:tt)*] [$($pred:tt)*] $data_type:ty;
+        version = $version:literal, migrate_from = $from:ty
+        $(, $($rest:tt)*)?) => {
+        impl<$($generics)*> $crate::storage::Versioned for $data_type
         where
-            K: $( $key_type + )? soroban_sdk::IntoVal<soroban_sdk::Env, soroban_sdk::Val>
+            $data_type: soroban_sdk::IntoVal<soroban_sdk::Env, soroban_sdk::Val>
                 + soroban_sdk::TryFromVal<soroban_sdk::Env, soroban_sdk::Val>,
+            $($pred)*
         {
-            fn get(&self, env: &soroban_sdk::Env) -> Option<$data_type> {
-                $crate::storage::with_temporary_storage(env, |storage| storage.get(self.get_key()))
-            }
+            const VERSION: u32 = $version;
 
-            fn set(&self, env: &soroban_sdk::Env, data: &$data_type) {
-                $crate::storage::with_temporary_storage(env, |storage| {
-                    storage.set(self.get_key(), data)
-                });
-            }
-
-            fn remove(&self, env: &soroban_sdk::Env) {
-                $crate::storage::with_temporary_storage(env, |storage| {
-                    storage.remove(self.get_key())
-                });
-            }
-
-            fn has(&self, env: &soroban_sdk::Env) -> bool {
-                $crate::storage::with_temporary_storage(env, |storage| storage.has(self.get_key()))
+            // Data stored with older versions is migrated hop by hop.
+            fn migrate(
+                env: &soroban_sdk::Env,
+                version: u32,
+                data: soroban_sdk::Val,
+            ) -> Option<Self> {
+                if version == Self::VERSION {
+                    soroban_sdk::TryFromVal::try_from_val(env, &data).ok()
+                } else {
+                    <$from as $crate::storage::Versioned>::migrate(env, version, data)
+                        .map(Self::from)
+                }
             }
+        }
+    };
+    (@versioned [$($generics:tt)*] [$($pred:tt)*] $data_type:ty;
+        version = $version:literal $(, $($rest:tt)*)?) => {
+        impl<$($generics)*> $crate::storage::Versioned for $data_type
+        where
+            $data_type: soroban_sdk::IntoVal<soroban_sdk::Env, soroban_sdk::Val>
+                + soroban_sdk::TryFromVal<soroban_sdk::Env, soroban_sdk::Val>,
+            $($pred)*
+        {
+            const VERSION: u32 = $version;
+        }
+    };
+    (@versioned [$($generics:tt)*] [$($pred:tt)*] $data_type:ty;
+        $option:ident $(($($args:tt)*))? $(, $($rest:tt)*)?) => {
+        $crate::impl_storage!(@versioned [$($generics)*] [$($pred)*] $data_type; $($($rest)*)?);
+    };
+    // @indexed
+    (@indexed [$($generics:tt)*] [$($pred:tt)*] $data_type:ty;) => {
+        impl<$($generics)*> $crate::storage::Indexed for $data_type where $($pred)* {}
+    };
+    (@indexed [$($generics:tt)*] [$($pred:tt)*] $data_type:ty;
+        index($($field:ident),+) $(, $($rest:tt)*)?) => {
+        impl<$($generics)*> $crate::storage::Indexed for $data_type where $($pred)* {
+            const INDEX: &'static str = stringify!($data_type);
 
-            fn extend_ttl(&self, env: &Env, threshold: u32, extend_to: u32) {
-                $crate::storage::with_temporary_storage(env, |storage| {
-                    storage.extend_ttl(
-                        self.get_key(),
-                        threshold,
-                        extend_to,
-                    )
-                });
+            fn reindex(
+                env: &soroban_sdk::Env,
+                tier: $crate::storage::StorageTier,
+                ttl: Option<$crate::storage::TtlPolicy>,
+                key: soroban_sdk::Val,
+                previous: Option<&Self>,
+                data: Option<&Self>,
+            ) {
+                $(
+                    let (previous_value, value) =
+                        (previous.map(|data| &data.$field), data.map(|data| &data.$field));
+                    if previous_value != value {
+                        if let Some(previous_value) = previous_value {
+                            $crate::storage::update_index::<Self, _>(env, tier, ttl,
+                                stringify!($field), previous_value, key, false);
+                        }
+                        if let Some(value) = value {
+                            $crate::storage::update_index::<Self, _>(env, tier, ttl,
+                                stringify!($field), value, key, true);
+                        }
+                    }
+                )+
             }
         }
     };
+    (@indexed [$($generics:tt)*] [$($pred:tt)*] $data_type:ty;
+        version = $version:literal, migrate_from = $from:ty
+        $(, $($rest:tt)*)?) => {
+        $crate::impl_storage!(@indexed [$($generics)*] [$($pred)*] $data_type; $($($rest)*)?);
+    };
+    (@indexed [$($generics:tt)*] [$($pred:tt)*] $data_type:ty;
+        version = $version:literal $(, $($rest:tt)*)?) => {
+        $crate::impl_storage!(@indexed [$($generics)*] [$($pred)*] $data_type; $($($rest)*)?);
+    };
+    (@indexed [$($generics:tt)*] [$($pred:tt)*] $data_type:ty;
+        $option:ident $(($($args:tt)*))? $(, $($rest:tt)*)?) => {
+        $crate::impl_storage!(@indexed [$($generics)*] [$($pred)*] $data_type; $($($rest)*)?);
+    };
 }
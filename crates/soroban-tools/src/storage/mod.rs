@@ -13,3 +13,28 @@
 #[macro_use]
 mod r#impl;
 pub use r#impl::*;
+
+mod collections;
+pub use collections::*;
+
+mod cache;
+pub use cache::*;
+
+mod tiers;
+pub use tiers::*;
+
+mod registry;
+pub use registry::*;
+
+mod hashed;
+pub use hashed::*;
+
+pub mod quota;
+
+mod packed;
+pub use packed::*;
+
+#[cfg(feature = "testutils")]
+mod snapshot;
+#[cfg(feature = "testutils")]
+pub use snapshot::*;
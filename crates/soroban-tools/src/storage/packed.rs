@@ -0,0 +1,61 @@
+/*
+    Copyright (c) 2023-2024 Frederic Kyung-jin Rezeau (오경진 吳景振)
+
+    This file is part of soroban-kit.
+
+    Licensed under the MIT License, this software is provided "AS IS",
+    no liability assumed. For details, see the LICENSE file in the
+    root directory.
+
+    Author: Fred Kyung-jin Rezeau <fred@litemint.com>
+*/
+
+
+use soroban_sdk::{Bytes, Env};
+
+/// Compact `Bytes` encoding of a data type (see `#[storage(.., packed)]`).
+///
+/// Packed data is laid out as its bit flags (bool fields) followed by its
+/// fixed-width fields, in declaration order.
+pub trait Packed: Sized {
+    /// Size of the packed data in bytes.
+    const SIZE: u32;
+
+    fn pack(&self, env: &Env) -> Bytes;
+
+    /// Decode packed data, None if the layout does not match.
+    fn unpack(bytes: &Bytes) -> Option<Self>;
+}
+
+/// Fixed-width field of a packed layout.
+pub trait PackedField: Sized {
+    /// Size of the field in bytes.
+    const SIZE: u32;
+
+    fn write(&self, bytes: &mut Bytes);
+
+    /// Read the field at `offset`, the bytes must hold `SIZE` bytes from it.
+    fn read(bytes: &Bytes, offset: u32) -> Self;
+}
+
+macro_rules! impl_packed_field {
+    ($($field_type:ty),*) => {
+        $(
+            impl PackedField for $field_type {
+                const SIZE: u32 = core::mem::size_of::<$field_type>() as u32;
+
+                fn write(&self, bytes: &mut Bytes) {
+                    bytes.extend_from_array(&self.to_be_bytes());
+                }
+
+                fn read(bytes: &Bytes, offset: u32) -> Self {
+                    let mut buffer = [0u8; core::mem::size_of::<$field_type>()];
+                    bytes.slice(offset..offset + Self::SIZE).copy_into_slice(&mut buffer);
+                    <$field_type>::from_be_bytes(buffer)
+                }
+            }
+        )*
+    };
+}
+
+impl_packed_field!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
@@ -0,0 +1,193 @@
+/*
+    Copyright (c) 2023-2024 Frederic Kyung-jin Rezeau (오경진 吳景振)
+
+    This file is part of soroban-kit.
+
+    Licensed under the MIT License, this software is provided "AS IS",
+    no liability assumed. For details, see the LICENSE file in the
+    root directory.
+
+    Author: Fred Kyung-jin Rezeau <fred@litemint.com>
+*/
+
+
+// Storage quotas limiting the entries and bytes written on behalf of an
+// account through the typed storage.
+use soroban_sdk::{
+    contracttype, symbol_short, xdr::ToXdr, Address, Env, IntoVal, Symbol, TryFromVal, Val,
+};
+
+use crate::error::KitError;
+use crate::storage::{
+    tier_bump, tier_extend_ttl, tier_get, tier_has, tier_remove, tier_set, StorageOps,
+    StorageProxy, StorageTier,
+};
+
+/// Storage quota of an account.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Quota {
+    pub max_entries: u32,
+    pub max_bytes: u32,
+}
+
+/// Entries and bytes written on behalf of an account.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Usage {
+    pub entries: u32,
+    pub bytes: u32,
+}
+
+// Quota records are kept in the persistent storage.
+const TIER: StorageTier = StorageTier::Persistent;
+
+fn limit_key(account: Option<&Address>) -> (Symbol, Option<Address>) {
+    (symbol_short!("quota"), account.cloned())
+}
+
+fn usage_key(account: &Address) -> (Symbol, Address) {
+    (symbol_short!("usage"), account.clone())
+}
+
+// Bytes charged to the account for the entry (tier, key).
+fn charge_key(account: &Address, tier: StorageTier, key: Val) -> (Symbol, Address, u32, Val) {
+    (symbol_short!("charge"), account.clone(), tier as u32, key)
+}
+
+/// Set the quota of `account`.
+pub fn set_limit(env: &Env, account: &Address, quota: &Quota) {
+    tier_set(env, TIER, &limit_key(Some(account)), quota);
+}
+
+/// Set the quota of the accounts without their own quota.
+pub fn set_default_limit(env: &Env, quota: &Quota) {
+    tier_set(env, TIER, &limit_key(None), quota);
+}
+
+/// Get the quota of `account`, None if unlimited.
+pub fn limit(env: &Env, account: &Address) -> Option<Quota> {
+    tier_get(env, TIER, &limit_key(Some(account)))
+        .or_else(|| tier_get(env, TIER, &limit_key(None)))
+}
+
+/// Get the entries and bytes written on behalf of `account`.
+pub fn usage(env: &Env, account: &Address) -> Usage {
+    tier_get(env, TIER, &usage_key(account)).unwrap_or_default()
+}
+
+fn set_usage(env: &Env, account: &Address, usage: &Usage) {
+    if *usage == Usage::default() {
+        tier_remove(env, TIER, &usage_key(account));
+    } else {
+        tier_set(env, TIER, &usage_key(account), usage);
+    }
+}
+
+/// Set the data on behalf of `account` or `KitError::QuotaExceeded`.
+///
+/// The account is charged one entry and the XDR size of the key and data,
+/// replacing data it was already charged for only updates the size.
+/// The charge and usage records follow the TTL policy of the data type
+/// (see `extend_ttl`).
+pub fn set<K, T>(env: &Env, account: &Address, key: &K, data: &T) -> Result<(), KitError>
+where
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+    T: IntoVal<Env, Val>,
+    for<'a> StorageProxy<'a, K, T>: StorageOps<T>,
+{
+    let key_val: Val = key.into_val(env);
+    let data_val: Val = data.into_val(env);
+    let bytes = key_val.to_xdr(env).len() + data_val.to_xdr(env).len();
+    let charge_key = charge_key(account, <StorageProxy<K, T> as StorageOps<T>>::TIER, key_val);
+    let charged: Option<u32> = tier_get(env, TIER, &charge_key);
+
+    let usage = usage(env, account);
+    let usage = Usage {
+        entries: usage
+            .entries
+            .checked_add(charged.is_none() as u32)
+            .ok_or(KitError::Overflow)?,
+        bytes: usage
+            .bytes
+            .checked_sub(charged.unwrap_or(0))
+            .ok_or(KitError::Underflow)?
+            .saturating_add(bytes),
+    };
+    if limit(env, account).is_some_and(|quota| {
+        usage.entries > quota.max_entries || usage.bytes > quota.max_bytes
+    }) {
+        return Err(KitError::QuotaExceeded);
+    }
+
+    StorageProxy::new(key).set(env, data);
+    tier_set(env, TIER, &charge_key, &bytes);
+    set_usage(env, account, &usage);
+    let ttl = <StorageProxy<K, T> as StorageOps<T>>::TTL;
+    tier_bump(env, TIER, &charge_key, ttl, true);
+    tier_bump(env, TIER, &usage_key(account), ttl, true);
+    Ok(())
+}
+
+/// Extend the TTL of the data along with the quota records of `account`,
+/// or `KitError::NotFound` if the data is missing.
+pub fn extend_ttl<K, T>(
+    env: &Env,
+    account: &Address,
+    key: &K,
+    threshold: u32,
+    extend_to: u32,
+) -> Result<(), KitError>
+where
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+    for<'a> StorageProxy<'a, K, T>: StorageOps<T>,
+{
+    StorageProxy::new(key).try_extend_ttl(env, threshold, extend_to)?;
+    let charge_key = charge_key(
+        account,
+        <StorageProxy<K, T> as StorageOps<T>>::TIER,
+        key.into_val(env),
+    );
+    if tier_has(env, TIER, &charge_key) {
+        tier_extend_ttl(env, TIER, &charge_key, threshold, extend_to);
+        tier_extend_ttl(env, TIER, &usage_key(account), threshold, extend_to);
+    }
+    Ok(())
+}
+
+/// Remove the data and release the quota charged to `account` for it.
+pub fn remove<K, T>(env: &Env, account: &Address, key: &K) -> Result<(), KitError>
+where
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+    for<'a> StorageProxy<'a, K, T>: StorageOps<T>,
+{
+    StorageProxy::new(key).remove(env);
+    release::<K, T>(env, account, key)?;
+    Ok(())
+}
+
+/// Release the quota charged to `account` for the data, e.g. after it was
+/// removed or expired. Returns false if the account was not charged for it,
+/// or `KitError::Underflow` if the usage is lower than the charge.
+pub fn release<K, T>(env: &Env, account: &Address, key: &K) -> Result<bool, KitError>
+where
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+    for<'a> StorageProxy<'a, K, T>: StorageOps<T>,
+{
+    let charge_key = charge_key(
+        account,
+        <StorageProxy<K, T> as StorageOps<T>>::TIER,
+        key.into_val(env),
+    );
+    let Some(charged) = tier_get::<_, u32>(env, TIER, &charge_key) else {
+        return Ok(false);
+    };
+    let usage = usage(env, account);
+    let usage = Usage {
+        entries: usage.entries.checked_sub(1).ok_or(KitError::Underflow)?,
+        bytes: usage.bytes.checked_sub(charged).ok_or(KitError::Underflow)?,
+    };
+    tier_remove(env, TIER, &charge_key);
+    set_usage(env, account, &usage);
+    Ok(true)
+}
@@ -0,0 +1,107 @@
+/*
+    Copyright (c) 2023-2024 Frederic Kyung-jin Rezeau (오경진 吳景振)
+
+    This file is part of soroban-kit.
+
+    Licensed under the MIT License, this software is provided "AS IS",
+    no liability assumed. For details, see the LICENSE file in the
+    root directory.
+
+    Author: Fred Kyung-jin Rezeau <fred@litemint.com>
+*/
+
+
+use soroban_sdk::{
+    contracttype, symbol_short, Env, IntoVal, String, Symbol, TryFromVal, Val, Vec,
+};
+
+use super::{StorageMap, StorageTier};
+
+// Registry entry: bit mask of the storage tiers holding data under the key.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KeyRegistryEntry(u32);
+
+impl_key_constraint!(((Symbol, String), Val), KeyRegistryConstraint);
+impl_storage!(Persistent, KeyRegistryEntry, KeyRegistryConstraint);
+
+/// Key type whose keys are recorded in a registry when data is written
+/// through the typed storage (see `impl_key_constraint!`).
+pub trait KeyRegistry {
+    /// Registry name.
+    const REGISTRY: &'static str;
+}
+
+fn registry_key(env: &Env, registry: &str) -> (Symbol, String) {
+    (symbol_short!("registry"), String::from_str(env, registry))
+}
+
+/// Record `key` as holding data in `tier` (or not, if `insert` is false).
+pub fn register_key(env: &Env, registry: &str, tier: StorageTier, key: Val, insert: bool) {
+    let registry_key = registry_key(env, registry);
+    let keys = StorageMap::<_, Val, KeyRegistryEntry>::new(&registry_key);
+    let tiers = keys.get(env, &key).map_or(0, |entry| entry.0);
+    let updated = if insert {
+        tiers | 1 << tier as u32
+    } else {
+        tiers & !(1 << tier as u32)
+    };
+    if updated == tiers {
+        return;
+    }
+    if updated == 0 {
+        keys.remove(env, &key);
+    } else {
+        keys.set(env, &key, &KeyRegistryEntry(updated));
+    }
+}
+
+/// Extend the TTL of the registry entry of `key` (no-op if not registered).
+pub fn extend_registered_key(env: &Env, registry: &str, key: Val, threshold: u32, extend_to: u32) {
+    let registry_key = registry_key(env, registry);
+    let keys = StorageMap::<_, Val, KeyRegistryEntry>::new(&registry_key);
+    // Not registered if missing.
+    let _ = keys.extend_ttl(env, &key, threshold, extend_to);
+}
+
+/// Extend the TTL of the registry entries of up to `limit` keys, starting
+/// from position `cursor`.
+pub fn extend_registry_ttl<K: KeyRegistry>(
+    env: &Env,
+    cursor: u32,
+    limit: u32,
+    threshold: u32,
+    extend_to: u32,
+) {
+    let registry_key = registry_key(env, K::REGISTRY);
+    let keys = StorageMap::<_, Val, KeyRegistryEntry>::new(&registry_key);
+    for key in keys.keys(env, cursor, limit) {
+        let _ = keys.extend_ttl(env, &key, threshold, extend_to);
+    }
+}
+
+/// Get up to `limit` registered keys holding data, starting from position
+/// `cursor`. Removing keys moves the last registered key into the freed
+/// position.
+///
+/// Keys are unregistered when their data is removed through the typed
+/// storage: keys of expired temporary data remain registered, check `has`
+/// before using them.
+pub fn keys<K>(env: &Env, cursor: u32, limit: u32) -> Vec<K>
+where
+    K: KeyRegistry + IntoVal<Env, Val> + TryFromVal<Env, Val>,
+{
+    let registry_key = registry_key(env, K::REGISTRY);
+    let mut keys = Vec::new(env);
+    for key in StorageMap::<_, Val, KeyRegistryEntry>::new(&registry_key).keys(env, cursor, limit) {
+        if let Ok(key) = K::try_from_val(env, &key) {
+            keys.push_back(key);
+        }
+    }
+    keys
+}
+
+/// Number of registered keys holding data.
+pub fn keys_len<K: KeyRegistry>(env: &Env) -> u32 {
+    StorageMap::<_, Val, KeyRegistryEntry>::new(&registry_key(env, K::REGISTRY)).len(env)
+}
@@ -0,0 +1,193 @@
+/*
+    Copyright (c) 2023-2024 Frederic Kyung-jin Rezeau (오경진 吳景振)
+
+    This file is part of soroban-kit.
+
+    Licensed under the MIT License, this software is provided "AS IS",
+    no liability assumed. For details, see the LICENSE file in the
+    root directory.
+
+    Author: Fred Kyung-jin Rezeau <fred@litemint.com>
+*/
+
+
+// Storage snapshots of the test environment, to inspect and diff the
+// contract data written by the typed storage.
+extern crate std;
+
+use core::fmt;
+use std::vec::Vec as StdVec;
+
+use soroban_sdk::{
+    xdr::{ContractDataDurability, LedgerEntryData, ScVal},
+    Address, Env, TryFromVal,
+};
+
+use super::StorageTier;
+
+/// Contract data entry captured by `snapshot`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnapshotEntry {
+    pub contract: Address,
+    pub tier: StorageTier,
+    pub key: ScVal,
+    pub value: ScVal,
+    /// Live-until ledger (of the contract instance for instance entries).
+    pub live_until: Option<u32>,
+}
+
+impl SnapshotEntry {
+    fn is_entry(&self, other: &SnapshotEntry) -> bool {
+        self.contract == other.contract && self.tier == other.tier && self.key == other.key
+    }
+}
+
+impl fmt::Display for SnapshotEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} {:?} {:?} = {:?}", self.contract, self.tier, self.key, self.value)?;
+        match self.live_until {
+            Some(live_until) => write!(f, " (live until {})", live_until),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Contract data entries of the environment.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Snapshot(pub StdVec<SnapshotEntry>);
+
+impl Snapshot {
+    /// Get the entry stored under `key` in `tier`.
+    pub fn get(&self, tier: StorageTier, key: &ScVal) -> Option<&SnapshotEntry> {
+        self.0.iter().find(|entry| entry.tier == tier && entry.key == *key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in &self.0 {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+/// Capture the contract data entries of the environment, with the instance
+/// storage of each contract expanded into its entries.
+///
+/// Entries are read from the host ledger, so data written through the
+/// `mock-storage` backend (which never reaches the host) is not captured.
+pub fn snapshot(env: &Env) -> Snapshot {
+    let mut entries = StdVec::new();
+    for (_, (entry, live_until)) in env.to_ledger_snapshot().entries() {
+        let LedgerEntryData::ContractData(data) = &entry.data else {
+            continue;
+        };
+        let contract = Address::try_from_val(env, &ScVal::Address(data.contract.clone())).unwrap();
+        match (&data.key, &data.val) {
+            (ScVal::LedgerKeyContractInstance, ScVal::ContractInstance(instance)) => {
+                for item in instance.storage.iter().flat_map(|storage| storage.iter()) {
+                    entries.push(SnapshotEntry {
+                        contract: contract.clone(),
+                        tier: StorageTier::Instance,
+                        key: item.key.clone(),
+                        value: item.val.clone(),
+                        live_until: *live_until,
+                    });
+                }
+            }
+            (key, value) => entries.push(SnapshotEntry {
+                contract,
+                tier: match data.durability {
+                    ContractDataDurability::Persistent => StorageTier::Persistent,
+                    ContractDataDurability::Temporary => StorageTier::Temporary,
+                },
+                key: key.clone(),
+                value: value.clone(),
+                live_until: *live_until,
+            }),
+        }
+    }
+    Snapshot(entries)
+}
+
+/// Change of a contract data entry between two snapshots.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    Added(SnapshotEntry),
+    Removed(SnapshotEntry),
+    Updated(SnapshotEntry, SnapshotEntry),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Added(entry) => write!(f, "+ {}", entry),
+            Change::Removed(entry) => write!(f, "- {}", entry),
+            Change::Updated(before, after) => {
+                write!(f, "~ {}", after)?;
+                if before.value != after.value {
+                    write!(f, " (was {:?})", before.value)?;
+                }
+                match (before.live_until, after.live_until) {
+                    (Some(before), Some(after)) if before != after => {
+                        write!(f, " (live until {} -> {})", before, after)
+                    }
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// Changes between two snapshots.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Diff(pub StdVec<Change>);
+
+impl Diff {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.0 {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compute the entries added, removed and updated (value or TTL) from
+/// `before` to `after`.
+pub fn diff(before: &Snapshot, after: &Snapshot) -> Diff {
+    let mut changes = StdVec::new();
+    for entry in &before.0 {
+        match after.0.iter().find(|other| entry.is_entry(other)) {
+            Some(other) if other != entry => {
+                changes.push(Change::Updated(entry.clone(), other.clone()))
+            }
+            Some(_) => {}
+            None => changes.push(Change::Removed(entry.clone())),
+        }
+    }
+    for entry in &after.0 {
+        if !before.0.iter().any(|other| entry.is_entry(other)) {
+            changes.push(Change::Added(entry.clone()));
+        }
+    }
+    Diff(changes)
+}
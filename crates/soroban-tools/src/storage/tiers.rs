@@ -0,0 +1,150 @@
+/*
+    Copyright (c) 2023-2024 Frederic Kyung-jin Rezeau (오경진 吳景振)
+
+    This file is part of soroban-kit.
+
+    Licensed under the MIT License, this software is provided "AS IS",
+    no liability assumed. For details, see the LICENSE file in the
+    root directory.
+
+    Author: Fred Kyung-jin Rezeau <fred@litemint.com>
+*/
+
+use soroban_sdk::{Env, IntoVal, TryFromVal, Val};
+
+use super::{StorageOps, StorageProxy, StorageTier, TtlPolicy};
+use crate::error::KitError;
+
+// Typed storage operations bound to an explicit tier rather than to the
+// data type tier, allowing the same data type to be stored in several tiers.
+macro_rules! impl_tier {
+    ($module:ident, $tier:ident) => {
+        pub mod $module {
+            use soroban_sdk::{Env, IntoVal, TryFromVal, Val};
+
+            use crate::error::KitError;
+            use crate::storage::{StorageOps, StorageProxy, StorageTier};
+
+            pub fn get<'a, K, T>(env: &Env, key: &'a K) -> Option<T>
+            where
+                StorageProxy<'a, K, T>: StorageOps<T>,
+                K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+            {
+                crate::storage::get_in(env, StorageTier::$tier, key)
+            }
+
+            /// Get the data or `KitError::NotFound` if missing.
+            pub fn try_get<'a, K, T>(env: &Env, key: &'a K) -> Result<T, KitError>
+            where
+                StorageProxy<'a, K, T>: StorageOps<T>,
+                K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+            {
+                get(env, key).ok_or(KitError::NotFound)
+            }
+
+            pub fn set<'a, K, T>(env: &Env, key: &'a K, data: &T)
+            where
+                StorageProxy<'a, K, T>: StorageOps<T>,
+                K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+            {
+                crate::storage::set_in(env, StorageTier::$tier, key, data);
+            }
+
+            pub fn has<'a, K, T>(env: &Env, key: &'a K) -> bool
+            where
+                StorageProxy<'a, K, T>: StorageOps<T>,
+                K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+            {
+                crate::storage::has_in(env, StorageTier::$tier, key)
+            }
+
+            pub fn remove<'a, K, T>(env: &Env, key: &'a K)
+            where
+                StorageProxy<'a, K, T>: StorageOps<T>,
+                K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+            {
+                crate::storage::remove_in(env, StorageTier::$tier, key);
+            }
+
+            pub fn extend_ttl<'a, K, T>(env: &Env, key: &'a K, threshold: u32, extend_to: u32)
+            where
+                StorageProxy<'a, K, T>: StorageOps<T>,
+                K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+            {
+                crate::storage::extend_ttl_in(env, StorageTier::$tier, key, threshold, extend_to);
+            }
+        }
+    };
+}
+
+impl_tier!(instance, Instance);
+impl_tier!(persistent, Persistent);
+impl_tier!(temporary, Temporary);
+
+/// TTL of an entry moved between tiers (see `move_tier`).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MoveTtl {
+    /// Start with the minimum TTL of the new tier (extended as on any write
+    /// of the data type).
+    Reset,
+    /// Carry over the TTL policy of the data type, extending the moved entry
+    /// as the policy does on read or write. Contracts cannot read the
+    /// remaining TTL of the source entry.
+    CarryOver,
+    /// Extend the moved entry with the given policy.
+    Extend(TtlPolicy),
+}
+
+/// Move the data stored under `key` from the tier `from` to the tier `to`.
+///
+/// The data is moved through the typed storage (see `StorageOps`), along
+/// with its registry and index entries. Fails with `KitError::NotFound` if
+/// `from` holds no data for the key and `KitError::AlreadyExists` if `to`
+/// already does.
+pub fn move_tier<'a, K, T>(
+    env: &Env,
+    key: &'a K,
+    from: StorageTier,
+    to: StorageTier,
+    ttl: MoveTtl,
+) -> Result<(), KitError>
+where
+    StorageProxy<'a, K, T>: StorageOps<T>,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+{
+    let proxy = StorageProxy::<'a, K, T>::new(key);
+    let data = proxy.get_in(env, from).ok_or(KitError::NotFound)?;
+    if proxy.has_in(env, to) {
+        return Err(KitError::AlreadyExists);
+    }
+    // Written first so that registered keys keep their position.
+    proxy.set_in(env, to, &data);
+    proxy.remove_in(env, from);
+    let policy = match ttl {
+        MoveTtl::Reset => None,
+        MoveTtl::CarryOver => <StorageProxy<K, T> as StorageOps<T>>::TTL,
+        MoveTtl::Extend(policy) => Some(policy),
+    };
+    if let Some(policy) = policy {
+        proxy.extend_ttl_in(env, to, policy.threshold, policy.extend_to);
+    }
+    Ok(())
+}
+
+/// Move the data from the temporary to the persistent storage (see `move_tier`).
+pub fn promote<'a, K, T>(env: &Env, key: &'a K, ttl: MoveTtl) -> Result<(), KitError>
+where
+    StorageProxy<'a, K, T>: StorageOps<T>,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+{
+    move_tier(env, key, StorageTier::Temporary, StorageTier::Persistent, ttl)
+}
+
+/// Move the data from the persistent to the temporary storage (see `move_tier`).
+pub fn demote<'a, K, T>(env: &Env, key: &'a K, ttl: MoveTtl) -> Result<(), KitError>
+where
+    StorageProxy<'a, K, T>: StorageOps<T>,
+    K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+{
+    move_tier(env, key, StorageTier::Persistent, StorageTier::Temporary, ttl)
+}
@@ -19,12 +19,18 @@ mod tests {
     use core::panic::AssertUnwindSafe;
     #[cfg(not(feature = "mock-storage"))]
     use soroban_sdk::{
-        contract, contractimpl, contracttype, testutils::Address as _, Address, Env, IntoVal,
-        TryFromVal, Val,
+        contract, contractimpl, contracttype, symbol_short, testutils::Address as _, vec, Address,
+        Env, IntoVal, Symbol, TryFromVal, Val,
     };
     #[cfg(feature = "mock-storage")]
-    use soroban_sdk::{contracttype, testutils::Address as _, Address, Env};
+    use soroban_sdk::{
+        contract, contracttype,
+        testutils::{Address as _, Ledger},
+        Address, Env, IntoVal,
+    };
 
+    #[cfg(not(feature = "mock-storage"))]
+    use soroban_tools::{error::KitError, impl_counter};
     use soroban_tools::{impl_key_constraint, impl_storage, storage};
 
     use std::panic::catch_unwind;
@@ -81,6 +87,21 @@ mod tests {
 
         // Verify that the data is no more available.
         assert_eq!(storage::has::<K, D>(&env, &key), false);
+
+        // Result-returning operations fail with kit error codes when no data is found.
+        assert_eq!(storage::try_get::<K, D>(env, key), Err(KitError::NotFound));
+        assert_eq!(storage::try_remove::<K, D>(env, key), Err(KitError::NotFound));
+        assert_eq!(
+            storage::try_extend_ttl::<K, D>(env, key, 1, 1),
+            Err(KitError::NotFound)
+        );
+
+        // And succeed otherwise.
+        assert_eq!(storage::try_set::<K, D>(env, key, &data), Ok(()));
+        assert_eq!(storage::try_extend_ttl::<K, D>(env, key, 1, 1), Ok(()));
+        assert_eq!(storage::try_get::<K, D>(env, key), Ok(data));
+        assert_eq!(storage::try_remove::<K, D>(env, key), Ok(()));
+        assert_eq!(storage::has::<K, D>(env, key), false);
     }
 
     // Admin key.
@@ -114,7 +135,40 @@ mod tests {
         pub address: Address,
     }
 
+    // Contract type for hit counts.
+    #[cfg(not(feature = "mock-storage"))]
+    #[contracttype]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct HitsData {
+        pub count: u32,
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    impl_storage!(Persistent, HitsData);
+
+    // Contract type for prices, extended on read.
+    #[cfg(not(feature = "mock-storage"))]
+    #[contracttype]
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct PriceData {
+        pub price: i128,
+    }
+
     #[cfg(not(feature = "mock-storage"))]
+    impl_storage!(Temporary, PriceData; ttl(threshold = 50, extend_to = 100, extend_on = read));
+
+    // Contract type for heap elements, ordered by price then id.
+    #[cfg(not(feature = "mock-storage"))]
+    #[contracttype]
+    #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+    pub struct OrderData {
+        pub price: i128,
+        pub id: u32,
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    impl_storage!(Persistent, OrderData);
+
     #[contract]
     pub struct TestContract;
 
@@ -179,6 +233,646 @@ mod tests {
                 },
             );
         }
+
+        pub fn test_storage_collections(env: Env) {
+            // Contract type for collection entries.
+            #[contracttype]
+            #[derive(Clone, Debug, Eq, PartialEq)]
+            pub struct EntryData {
+                pub value: u32,
+            }
+
+            // Collections use the storage implemented for the element type.
+            impl_storage!(Persistent, EntryData);
+
+            let key = UserKey::Session(1);
+            let entry = |value| EntryData { value };
+
+            // Vector elements are spread across entries derived from the key.
+            let list = storage::StorageVec::<UserKey, EntryData>::new(&key);
+            assert!(list.is_empty(&env));
+            assert_eq!(list.push(&env, &entry(1)), 0);
+            assert_eq!(list.push(&env, &entry(2)), 1);
+            assert_eq!(list.push(&env, &entry(3)), 2);
+            assert_eq!(list.len(&env), 3);
+            assert_eq!(list.get(&env, 1), Some(entry(2)));
+            assert_eq!(list.get(&env, 3), None);
+            assert_eq!(list.set(&env, 1, &entry(20)), Ok(()));
+            assert_eq!(list.set(&env, 3, &entry(4)), Err(KitError::OutOfBounds));
+            assert_eq!(list.page(&env, 1, 10), vec![&env, entry(20), entry(3)]);
+            assert_eq!(list.page(&env, 0, 1), vec![&env, entry(1)]);
+            assert_eq!(list.pop(&env), Some(entry(3)));
+            assert_eq!(list.len(&env), 2);
+            assert_eq!(list.extend_ttl(&env, 1, 1, 1), Ok(()));
+            assert_eq!(list.extend_ttl(&env, 2, 1, 1), Err(KitError::OutOfBounds));
+            list.clear(&env);
+            assert!(list.is_empty(&env));
+            assert_eq!(list.pop(&env), None);
+
+            // Map entries keep track of their keys for paginated iteration.
+            let map = storage::StorageMap::<UserKey, u32, EntryData>::new(&key);
+            map.set(&env, &10, &entry(1));
+            map.set(&env, &20, &entry(2));
+            map.set(&env, &30, &entry(3));
+            map.set(&env, &20, &entry(4));
+            assert_eq!(map.len(&env), 3);
+            assert!(map.has(&env, &20));
+            assert_eq!(map.get(&env, &20), Some(entry(4)));
+            assert_eq!(storage::get(&env, &(key.clone(), 20_u32)), Some(entry(4)));
+            assert_eq!(map.keys(&env, 0, 10), vec![&env, 10, 20, 30]);
+            assert!(map.remove(&env, &10));
+            assert!(!map.remove(&env, &10));
+            assert!(!map.has(&env, &10));
+            assert_eq!(map.get(&env, &10), None);
+            assert_eq!(map.keys(&env, 0, 10), vec![&env, 30, 20]);
+            assert_eq!(map.page(&env, 1, 10), vec![&env, (20, entry(4))]);
+            assert_eq!(map.extend_ttl(&env, &30, 1, 1), Ok(()));
+            assert_eq!(map.extend_ttl(&env, &10, 1, 1), Err(KitError::NotFound));
+            assert!(map.remove(&env, &20));
+            assert!(map.remove(&env, &30));
+            assert!(map.is_empty(&env));
+
+            // Deque elements can be pushed and popped at both ends.
+            let deque = storage::StorageDeque::<UserKey, EntryData>::new(&key);
+            deque.push_back(&env, &entry(2));
+            deque.push_front(&env, &entry(1));
+            deque.push_back(&env, &entry(3));
+            assert_eq!(deque.len(&env), 3);
+            assert_eq!(deque.front(&env), Some(entry(1)));
+            assert_eq!(deque.back(&env), Some(entry(3)));
+            assert_eq!(deque.get(&env, 1), Some(entry(2)));
+            assert_eq!(deque.get(&env, 3), None);
+            assert_eq!(deque.page(&env, 1, 10), vec![&env, entry(2), entry(3)]);
+            assert_eq!(deque.pop_front(&env), Some(entry(1)));
+            assert_eq!(deque.pop_back(&env), Some(entry(3)));
+            deque.clear(&env);
+            assert!(deque.is_empty(&env));
+            assert_eq!(deque.pop_front(&env), None);
+            assert_eq!(deque.back(&env), None);
+
+            // Missing elements are not popped and the deque is unchanged.
+            deque.push_back(&env, &entry(1));
+            deque.push_back(&env, &entry(2));
+            storage::remove::<_, EntryData>(&env, &(key.clone(), 0_u32));
+            assert_eq!(deque.pop_front(&env), None);
+            assert_eq!(deque.len(&env), 2);
+            assert_eq!(deque.pop_back(&env), Some(entry(2)));
+            assert_eq!(deque.len(&env), 1);
+            deque.clear(&env);
+
+            // Heap elements are popped greatest first.
+            let heap = storage::StorageHeap::<UserKey, OrderData>::new(&key);
+            let order = |price, id| OrderData { price, id };
+            heap.push(&env, &order(10, 1));
+            heap.push(&env, &order(30, 2));
+            heap.push(&env, &order(20, 3));
+            heap.push(&env, &order(30, 0));
+            assert_eq!(heap.len(&env), 4);
+            assert_eq!(heap.peek(&env), Some(order(30, 2)));
+            assert_eq!(heap.page(&env, 0, 1), vec![&env, order(30, 2)]);
+            assert_eq!(heap.pop(&env), Some(order(30, 2)));
+            assert_eq!(heap.pop(&env), Some(order(30, 0)));
+            assert_eq!(heap.pop(&env), Some(order(20, 3)));
+            heap.clear(&env);
+            assert!(heap.is_empty(&env));
+            assert_eq!(heap.pop(&env), None);
+            assert_eq!(heap.try_pop(&env), Ok(None));
+
+            // Missing elements are reported and the heap is unchanged.
+            heap.push(&env, &order(10, 1));
+            heap.push(&env, &order(20, 2));
+            heap.push(&env, &order(5, 3));
+            storage::remove::<_, OrderData>(&env, &(key.clone(), 1_u32));
+            assert_eq!(heap.try_pop(&env), Err(KitError::NotFound));
+            assert_eq!(heap.len(&env), 3);
+            assert_eq!(heap.page(&env, 0, 10), vec![&env, order(20, 2), order(5, 3)]);
+            storage::remove::<_, OrderData>(&env, &(key.clone(), 0_u32));
+            storage::set(&env, &(key.clone(), 1_u32), &order(10, 1));
+            assert_eq!(heap.try_push(&env, &order(30, 4)), Err(KitError::NotFound));
+            assert_eq!(heap.len(&env), 3);
+            assert_eq!(heap.page(&env, 0, 10), vec![&env, order(10, 1), order(5, 3)]);
+            heap.clear(&env);
+        }
+
+        pub fn test_storage_combinators(env: Env) {
+            // Contract types for a score and a counter.
+            #[contracttype]
+            #[derive(Clone, Debug, Eq, PartialEq)]
+            pub struct ScoreData {
+                pub points: u32,
+            }
+
+            #[contracttype]
+            #[derive(Clone, Debug, Eq, PartialEq)]
+            pub struct CounterData(pub u32);
+
+            impl_storage!(Persistent, ScoreData);
+            impl_storage!(Temporary, CounterData);
+            impl_counter!(CounterData, u32);
+
+            let key = UserKey::Session(2);
+            let score = |points| ScoreData { points };
+
+            // Read-modify-write.
+            let add = |data: Option<ScoreData>| score(data.map_or(0, |data| data.points) + 5);
+            assert_eq!(storage::update(&env, &key, add), score(5));
+            assert_eq!(storage::update(&env, &key, add), score(10));
+
+            // Initialization only applies to missing data.
+            assert_eq!(storage::get_or_init(&env, &key, || score(0)), score(10));
+            assert_eq!(storage::take::<_, ScoreData>(&env, &key), Some(score(10)));
+            assert_eq!(storage::take::<_, ScoreData>(&env, &key), None);
+            assert_eq!(storage::get_or_init(&env, &key, || score(1)), score(1));
+
+            // Replace returns the previous data.
+            assert_eq!(storage::replace(&env, &key, &score(2)), Some(score(1)));
+            storage::remove::<_, ScoreData>(&env, &key);
+            assert_eq!(storage::replace(&env, &key, &score(3)), None);
+
+            // Checked counters start from zero.
+            assert_eq!(storage::increment(&env, &key, 2), Ok(CounterData(2)));
+            assert_eq!(storage::decrement(&env, &key, 1), Ok(CounterData(1)));
+            assert_eq!(
+                storage::decrement::<_, CounterData>(&env, &key, 2),
+                Err(KitError::Underflow)
+            );
+            assert_eq!(
+                storage::increment::<_, CounterData>(&env, &key, u32::MAX),
+                Err(KitError::Overflow)
+            );
+            assert_eq!(storage::get(&env, &key), Some(CounterData(1)));
+        }
+
+        pub fn test_storage_cache(env: Env) {
+            let key = UserKey::Session(3);
+            let hits = |count| HitsData { count };
+
+            // Writes are deferred until flushed.
+            let mut cache = storage::Cache::new(&env);
+            assert_eq!(cache.get::<_, HitsData>(&key), None);
+            cache.set(&key, &hits(1));
+            assert_eq!(cache.get(&key), Some(hits(1)));
+            assert!(cache.has::<_, HitsData>(&key));
+            assert!(!storage::has::<_, HitsData>(&env, &key));
+            cache.flush();
+            assert_eq!(storage::get(&env, &key), Some(hits(1)));
+
+            // Removals are deferred too.
+            cache.remove::<_, HitsData>(&key);
+            assert!(!cache.has::<_, HitsData>(&key));
+            assert!(storage::has::<_, HitsData>(&env, &key));
+
+            cache.set(&key, &hits(2));
+            cache.flush();
+            assert_eq!(storage::get(&env, &key), Some(hits(2)));
+
+            // Dirty entries are flushed when the cache is dropped.
+            cache.set(&key, &hits(3));
+            drop(cache);
+            assert_eq!(storage::get(&env, &key), Some(hits(3)));
+        }
+
+        pub fn cache_price(env: Env, price: i128) {
+            let mut cache = storage::Cache::new(&env);
+            cache.set(&UserKey::Session(5), &PriceData { price });
+            assert_eq!(cache.get(&UserKey::Session(5)), Some(PriceData { price }));
+            cache.flush();
+        }
+
+        pub fn count_hits(env: Env, times: u32) {
+            for _ in 0..times {
+                storage::update(&env, &UserKey::Session(4), |data: Option<HitsData>| {
+                    HitsData {
+                        count: data.map_or(0, |data| data.count) + 1,
+                    }
+                });
+            }
+        }
+
+        pub fn count_hits_cached(env: Env, times: u32) {
+            let mut cache = storage::Cache::new(&env);
+            for _ in 0..times {
+                let data: Option<HitsData> = cache.get(&UserKey::Session(4));
+                cache.set(
+                    &UserKey::Session(4),
+                    &HitsData {
+                        count: data.map_or(0, |data| data.count) + 1,
+                    },
+                );
+            }
+            cache.flush();
+        }
+
+        pub fn get_hits(env: Env) -> u32 {
+            storage::get::<_, HitsData>(&env, &UserKey::Session(4))
+                .unwrap()
+                .count
+        }
+
+        pub fn test_storage_quota(env: Env) {
+            use storage::quota::{self, Quota, Usage};
+
+            // Contract type for note data.
+            #[contracttype]
+            #[derive(Clone, Debug, Eq, PartialEq)]
+            pub struct NoteData {
+                pub text: u32,
+            }
+
+            impl_storage!(Persistent, NoteData);
+
+            let (alice, bob) = (Address::generate(&env), Address::generate(&env));
+            let note = |text| NoteData { text };
+            quota::set_default_limit(
+                &env,
+                &Quota {
+                    max_entries: 2,
+                    max_bytes: 1024,
+                },
+            );
+            quota::set_limit(
+                &env,
+                &bob,
+                &Quota {
+                    max_entries: 1,
+                    max_bytes: 1024,
+                },
+            );
+
+            // Writes are charged to the account up to its quota.
+            assert_eq!(quota::set(&env, &alice, &UserKey::Session(10), &note(1)), Ok(()));
+            let usage = quota::usage(&env, &alice);
+            assert_eq!(usage.entries, 1);
+            assert_eq!(quota::set(&env, &alice, &UserKey::Session(10), &note(2)), Ok(()));
+            assert_eq!(quota::usage(&env, &alice), usage);
+            assert_eq!(quota::set(&env, &alice, &UserKey::Session(11), &note(3)), Ok(()));
+            assert_eq!(
+                quota::set(&env, &alice, &UserKey::Session(12), &note(4)),
+                Err(KitError::QuotaExceeded)
+            );
+            assert!(!storage::has::<_, NoteData>(&env, &UserKey::Session(12)));
+            assert_eq!(quota::set(&env, &bob, &UserKey::Session(12), &note(4)), Ok(()));
+            assert_eq!(
+                quota::set(&env, &bob, &UserKey::Session(13), &note(5)),
+                Err(KitError::QuotaExceeded)
+            );
+
+            // Removing releases the quota.
+            assert_eq!(quota::remove::<_, NoteData>(&env, &alice, &UserKey::Session(10)), Ok(()));
+            assert!(!storage::has::<_, NoteData>(&env, &UserKey::Session(10)));
+            assert_eq!(quota::usage(&env, &alice).entries, 1);
+            assert_eq!(quota::set(&env, &alice, &UserKey::Session(12), &note(4)), Ok(()));
+            storage::remove::<_, NoteData>(&env, &UserKey::Session(11));
+            assert_eq!(quota::release::<_, NoteData>(&env, &alice, &UserKey::Session(11)), Ok(true));
+            assert_eq!(quota::release::<_, NoteData>(&env, &alice, &UserKey::Session(11)), Ok(false));
+
+            // Quota records are extended along with the data.
+            assert_eq!(
+                quota::extend_ttl::<_, NoteData>(&env, &alice, &UserKey::Session(12), 100, 1000),
+                Ok(())
+            );
+            assert_eq!(
+                quota::extend_ttl::<_, NoteData>(&env, &alice, &UserKey::Session(11), 100, 1000),
+                Err(KitError::NotFound)
+            );
+            assert_eq!(quota::remove::<_, NoteData>(&env, &alice, &UserKey::Session(12)), Ok(()));
+            assert_eq!(quota::usage(&env, &alice), Usage::default());
+
+            // Byte limits apply too.
+            quota::set_limit(
+                &env,
+                &alice,
+                &Quota {
+                    max_entries: 10,
+                    max_bytes: usage.bytes,
+                },
+            );
+            assert_eq!(quota::set(&env, &alice, &UserKey::Session(10), &note(1)), Ok(()));
+            assert_eq!(
+                quota::set(&env, &alice, &UserKey::Session(11), &note(1)),
+                Err(KitError::QuotaExceeded)
+            );
+        }
+
+        pub fn test_storage_promotion(env: Env) {
+            // Contract type for order data, pending orders are temporary.
+            #[contracttype]
+            #[derive(Clone, Debug, Eq, PartialEq)]
+            pub struct OrderData {
+                pub amount: i128,
+            }
+
+            impl_storage!(Temporary, OrderData);
+
+            let (key, order) = (UserKey::Session(20), OrderData { amount: 100 });
+            let ttl = storage::MoveTtl::Extend(storage::TtlPolicy::new(10, 100));
+
+            // Confirmed orders are moved to the persistent storage.
+            storage::temporary::set(&env, &key, &order);
+            assert_eq!(storage::promote::<_, OrderData>(&env, &key, ttl), Ok(()));
+            assert!(!storage::temporary::has::<_, OrderData>(&env, &key));
+            assert_eq!(storage::persistent::get(&env, &key), Some(order.clone()));
+            assert_eq!(
+                storage::promote::<_, OrderData>(&env, &key, storage::MoveTtl::Reset),
+                Err(KitError::NotFound)
+            );
+
+            // The destination is never overwritten.
+            storage::temporary::set(&env, &key, &OrderData { amount: 200 });
+            assert_eq!(
+                storage::demote::<_, OrderData>(&env, &key, storage::MoveTtl::Reset),
+                Err(KitError::AlreadyExists)
+            );
+            storage::temporary::remove::<_, OrderData>(&env, &key);
+            assert_eq!(
+                storage::demote::<_, OrderData>(&env, &key, storage::MoveTtl::CarryOver),
+                Ok(())
+            );
+            assert_eq!(storage::temporary::get(&env, &key), Some(order.clone()));
+            assert!(!storage::persistent::has::<_, OrderData>(&env, &key));
+
+            // Any tiers.
+            assert_eq!(
+                storage::move_tier::<_, OrderData>(
+                    &env,
+                    &key,
+                    storage::StorageTier::Temporary,
+                    storage::StorageTier::Instance,
+                    storage::MoveTtl::Reset
+                ),
+                Ok(())
+            );
+            assert_eq!(storage::instance::get(&env, &key), Some(order));
+        }
+
+        pub fn test_storage_tiers(env: Env) {
+            // Contract type for message data, bound to the temporary storage.
+            #[contracttype]
+            #[derive(Clone, Debug, Eq, PartialEq)]
+            pub struct MessageData {
+                pub text: u32,
+            }
+
+            impl_storage!(Temporary, MessageData; version = 1);
+
+            let key = UserKey::Session(5);
+            let (cached, archived) = (MessageData { text: 1 }, MessageData { text: 2 });
+
+            // The same data type lives in several tiers under the same key.
+            storage::set(&env, &key, &cached);
+            storage::persistent::set(&env, &key, &archived);
+            assert_eq!(storage::get(&env, &key), Some(cached.clone()));
+            assert_eq!(storage::temporary::get(&env, &key), Some(cached));
+            assert_eq!(storage::persistent::get(&env, &key), Some(archived.clone()));
+            assert!(!storage::instance::has::<_, MessageData>(&env, &key));
+            assert_eq!(
+                storage::instance::try_get::<_, MessageData>(&env, &key),
+                Err(KitError::NotFound)
+            );
+
+            // Tier overrides go through the typed storage of the data type.
+            let stored: (Symbol, u32, Val) = env.storage().persistent().get(&key).unwrap();
+            assert_eq!((stored.0, stored.1), (symbol_short!("v"), 1));
+
+            // Each tier is operated independently.
+            storage::persistent::extend_ttl::<_, MessageData>(&env, &key, 1, 1);
+            storage::temporary::remove::<_, MessageData>(&env, &key);
+            assert!(!storage::has::<_, MessageData>(&env, &key));
+            assert_eq!(storage::persistent::try_get(&env, &key), Ok(archived));
+        }
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_tools_storage_tiers() {
+        let env = Env::default();
+        TestContractClient::new(&env, &env.register_contract(None, TestContract))
+            .test_storage_tiers();
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_tools_storage_quota() {
+        let env = Env::default();
+        TestContractClient::new(&env, &env.register_contract(None, TestContract))
+            .test_storage_quota();
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_tools_storage_promotion() {
+        let env = Env::default();
+        TestContractClient::new(&env, &env.register_contract(None, TestContract))
+            .test_storage_promotion();
+    }
+
+    #[cfg(all(feature = "testutils", not(feature = "mock-storage")))]
+    #[test]
+    fn test_tools_storage_snapshot() {
+        use soroban_sdk::{testutils::Ledger, xdr::ScVal, IntoVal, TryFromVal, Val};
+        use storage::{Change, StorageTier};
+
+        let env = Env::default();
+        let client = TestContractClient::new(&env, &env.register_contract(None, TestContract));
+        let to_scval = |val: Val| ScVal::try_from_val(&env, &val).unwrap();
+        let key = to_scval(UserKey::Session(4).into_val(&env));
+        let hits = |count: u32| to_scval(HitsData { count }.into_val(&env));
+
+        // New entries are captured with their decoded value and TTL.
+        let before = storage::snapshot(&env);
+        client.count_hits(&2);
+        let after = storage::snapshot(&env);
+        let entry = after.get(StorageTier::Persistent, &key).unwrap();
+        assert_eq!(entry.value, hits(2));
+        assert!(entry.live_until.is_some());
+        let diff = storage::diff(&before, &after);
+        assert_eq!(diff.0, std::vec![Change::Added(entry.clone())]);
+        assert!(diff.to_string().starts_with("+ "));
+
+        // Updates report the previous value.
+        client.count_hits(&1);
+        let diff = storage::diff(&after, &storage::snapshot(&env));
+        match diff.0.as_slice() {
+            [Change::Updated(before, after)] => {
+                assert_eq!((&before.value, &after.value), (&hits(2), &hits(3)));
+            }
+            changes => panic!("Unexpected changes {:?}", changes),
+        }
+        assert!(diff.to_string().contains("(was "));
+
+        // TTL extensions are changes too.
+        let before = storage::snapshot(&env);
+        env.ledger().with_mut(|li| li.sequence_number += 10);
+        client.test_storage_tiers();
+        assert!(storage::diff(&before, &before).is_empty());
+        assert!(!storage::diff(&before, &storage::snapshot(&env)).is_empty());
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_tools_storage_cache() {
+        let env = Env::default();
+        TestContractClient::new(&env, &env.register_contract(None, TestContract))
+            .test_storage_cache();
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_tools_storage_cache_ttl() {
+        use soroban_sdk::xdr::{ContractDataDurability, LedgerKey};
+
+        let env = Env::default();
+        let client = TestContractClient::new(&env, &env.register_contract(None, TestContract));
+
+        // Reading cached writes extends the TTL once flushed.
+        client.cache_price(&10);
+        let live_until = env
+            .to_ledger_snapshot()
+            .entries()
+            .into_iter()
+            .find_map(|(key, (_, live_until))| match key.as_ref() {
+                LedgerKey::ContractData(data)
+                    if data.durability == ContractDataDurability::Temporary =>
+                {
+                    *live_until
+                }
+                _ => None,
+            });
+        assert_eq!(live_until, Some(env.ledger().sequence() + 100));
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_tools_storage_cache_budget() {
+        let env = Env::default();
+        let client = TestContractClient::new(&env, &env.register_contract(None, TestContract));
+
+        env.budget().reset_default();
+        client.count_hits(&20);
+        let direct_cost = env.budget().cpu_instruction_cost();
+        assert_eq!(client.get_hits(), 20);
+
+        // Same result with fewer host calls.
+        env.budget().reset_default();
+        client.count_hits_cached(&20);
+        let cached_cost = env.budget().cpu_instruction_cost();
+        assert_eq!(client.get_hits(), 40);
+        assert!(cached_cost < direct_cost);
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_tools_storage_combinators() {
+        let env = Env::default();
+        TestContractClient::new(&env, &env.register_contract(None, TestContract))
+            .test_storage_combinators();
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_tools_storage_collections() {
+        let env = Env::default();
+        TestContractClient::new(&env, &env.register_contract(None, TestContract))
+            .test_storage_collections();
+    }
+
+    // Random operation sequences checked against std collections.
+    #[cfg(not(feature = "mock-storage"))]
+    fn run_model_tests<F>(f: F)
+    where
+        F: Fn(&Env, &mut rand::rngs::StdRng),
+    {
+        use rand::SeedableRng;
+
+        let env = Env::default();
+        env.budget().reset_unlimited();
+        let contract_id = env.register_contract(None, TestContract);
+        for seed in 0..8 {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            env.as_contract(&contract_id, || f(&env, &mut rng));
+        }
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_tools_storage_deque_model() {
+        use rand::Rng;
+        use std::collections::VecDeque;
+
+        run_model_tests(|env, rng| {
+            let key = UserKey::Session(rng.gen());
+            let deque = storage::StorageDeque::<UserKey, HitsData>::new(&key);
+            let mut model = VecDeque::new();
+            for _ in 0..200 {
+                let count = rng.gen_range(0..100);
+                match rng.gen_range(0..6) {
+                    0 | 1 => {
+                        deque.push_back(env, &HitsData { count });
+                        model.push_back(HitsData { count });
+                    }
+                    2 => {
+                        deque.push_front(env, &HitsData { count });
+                        model.push_front(HitsData { count });
+                    }
+                    3 => assert_eq!(deque.pop_front(env), model.pop_front()),
+                    4 => assert_eq!(deque.pop_back(env), model.pop_back()),
+                    _ => {
+                        let (start, limit) = (rng.gen_range(0..20), rng.gen_range(0..20));
+                        let page = deque.page(env, start, limit);
+                        let expected = model.iter().skip(start as usize).take(limit as usize);
+                        assert!(page.iter().eq(expected.cloned()));
+                    }
+                }
+                assert_eq!(deque.len(env), model.len() as u32);
+                assert_eq!(deque.front(env).as_ref(), model.front());
+                assert_eq!(deque.back(env).as_ref(), model.back());
+            }
+            deque.clear(env);
+            assert!(deque.is_empty(env));
+        });
+    }
+
+    #[cfg(not(feature = "mock-storage"))]
+    #[test]
+    fn test_tools_storage_heap_model() {
+        use rand::Rng;
+        use std::collections::BinaryHeap;
+
+        run_model_tests(|env, rng| {
+            let key = UserKey::Session(rng.gen());
+            let heap = storage::StorageHeap::<UserKey, OrderData>::new(&key);
+            let mut model = BinaryHeap::new();
+            for id in 0..200 {
+                if rng.gen_bool(0.6) {
+                    let order = OrderData {
+                        price: rng.gen_range(-50..50),
+                        id,
+                    };
+                    heap.push(env, &order);
+                    model.push(order);
+                } else {
+                    assert_eq!(heap.pop(env), model.pop());
+                }
+                assert_eq!(heap.len(env), model.len() as u32);
+                assert_eq!(heap.peek(env).as_ref(), model.peek());
+            }
+
+            // Pages are bounded and hold every element once.
+            let len = heap.len(env);
+            let mut elements = std::vec::Vec::new();
+            for start in (0..len).step_by(16) {
+                let page = heap.page(env, start, 16);
+                assert!(page.len() <= 16);
+                elements.extend(page.iter());
+            }
+            elements.sort();
+            assert_eq!(elements, model.clone().into_sorted_vec());
+
+            while let Some(order) = model.pop() {
+                assert_eq!(heap.pop(env), Some(order));
+            }
+            assert!(heap.is_empty(env));
+        });
     }
 
     #[cfg(not(feature = "mock-storage"))]
@@ -225,41 +919,235 @@ mod tests {
         impl_storage!(Instance, TestData, TestKeyConstraint);
 
         let env = Env::default();
-        let key = &TestKey::Session(Address::generate(&env));
-        let data = TestData {
-            address: Address::generate(&env),
-        };
+        let contract_id = env.register_contract(None, TestContract);
+        env.as_contract(&contract_id, || {
+            let key = &TestKey::Session(Address::generate(&env));
+            let data = TestData {
+                address: Address::generate(&env),
+            };
 
-        // Try to get the data, should panic.
-        let result = catch_unwind(AssertUnwindSafe(|| {
+            // Try to get the data, should panic.
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                assert_eq!(storage::get::<TestKey, TestData>(&env, &key).unwrap(), data);
+            }));
+            assert!(
+                result.is_err(),
+                "No data found. The operation should panic."
+            );
+
+            // Try to get the data with error tolerance, should not panic.
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                storage::get_or_else::<TestKey, TestData, _, _>(&env, &key, |opt| {
+                    opt.unwrap_or_else(|| data.clone())
+                });
+            }));
+            assert!(
+                !result.is_err(),
+                "No data found. The operation should not panic."
+            );
+
+            // Set the data.
+            storage::set::<TestKey, TestData>(&env, &key, &data);
+
+            // Verify that the storage now has the data.
+            assert_eq!(storage::has::<TestKey, TestData>(&env, &key), true);
+
+            // Extend data TTL.
+            storage::extend_ttl::<TestKey, TestData>(&env, &key, 1, 1);
+
+            // Get the data (unwrap).
             assert_eq!(storage::get::<TestKey, TestData>(&env, &key).unwrap(), data);
-        }));
-        assert!(
-            result.is_err(),
-            "No data found. The operation should panic."
-        );
 
-        // Try to get the data with error tolerance, should not panic.
-        let result = catch_unwind(AssertUnwindSafe(|| {
-            storage::get_or_else::<TestKey, TestData, _, _>(&env, &key, |opt| {
-                opt.unwrap_or_else(|| data.clone())
+            // Reading the data as another type finds nothing.
+            assert!(soroban_tools::mock_storage::with_instance_storage(&env, |storage| {
+                storage.get::<TestKey, u32>(&key)
+            })
+            .is_none());
+
+            // Remove the data.
+            storage::remove::<TestKey, TestData>(&env, &key);
+
+            // Verify that the data is no more available.
+            assert!(!storage::has::<TestKey, TestData>(&env, &key));
+            storage::set::<TestKey, TestData>(&env, &key, &data);
+        });
+
+        // Environments created later on do not see the data.
+        drop(env);
+        let env = Env::default();
+        let contract_id = env.register_contract(None, TestContract);
+        let key = &TestKey::Session(Address::generate(&env));
+        env.as_contract(&contract_id, || {
+            assert!(!storage::has::<TestKey, TestData>(&env, &key));
+        });
+    }
+
+    #[cfg(all(feature = "mock-storage", feature = "testutils"))]
+    #[test]
+    fn test_tools_mock_storage_ttl() {
+        use soroban_tools::mock_storage::{self, MockEntryState, MockStorageType};
+
+        #[contracttype]
+        #[derive(Clone, Debug, Eq, PartialEq)]
+        pub struct QuoteData {
+            pub price: i128,
+        }
+
+        #[contracttype]
+        #[derive(Clone, Debug, Eq, PartialEq)]
+        pub struct BalanceData {
+            pub amount: i128,
+        }
+
+        #[contracttype]
+        #[derive(Clone, Debug, Eq, PartialEq)]
+        pub enum TestKey {
+            Quote,
+            Balance,
+        }
+
+        impl_storage!(Temporary, QuoteData);
+        impl_storage!(Persistent, BalanceData);
+
+        let env = Env::default();
+        let contract_id = env.register_contract(None, TestContract);
+        env.as_contract(&contract_id, || {
+            env.ledger().with_mut(|li| {
+                li.min_temp_entry_ttl = 16;
+                li.min_persistent_entry_ttl = 100;
             });
-        }));
-        assert!(
-            !result.is_err(),
-            "No data found. The operation should not panic."
-        );
 
-        // Set the data.
-        storage::set::<TestKey, TestData>(&env, &key, &data);
+            // New entries live for the minimum TTL.
+            storage::set(&env, &TestKey::Quote, &QuoteData { price: 10 });
+            storage::set(&env, &TestKey::Balance, &BalanceData { amount: 20 });
+            assert_eq!(
+                mock_storage::get_entry_state(&env, MockStorageType::Temporary, &TestKey::Quote),
+                Some(MockEntryState::Live(15))
+            );
+            assert_eq!(
+                mock_storage::get_entry_state(&env, MockStorageType::Persistent, &TestKey::Balance),
+                Some(MockEntryState::Live(99))
+            );
 
-        // Verify that the storage now has the data.
-        assert_eq!(storage::has::<TestKey, TestData>(&env, &key), true);
+            // Storage is scoped to the environment.
+            let other_env = Env::default();
+            let other_id = other_env.register_contract(None, TestContract);
+            other_env.as_contract(&other_id, || {
+                assert!(!storage::has::<_, QuoteData>(&other_env, &TestKey::Quote));
+            });
 
-        // Extend data TTL.
-        storage::extend_ttl::<TestKey, TestData>(&env, &key, 1, 1);
+            // Extending the TTL is simulated against the ledger sequence.
+            env.ledger().with_mut(|li| li.sequence_number += 10);
+            storage::extend_ttl::<_, QuoteData>(&env, &TestKey::Quote, 10, 50);
+            assert_eq!(
+                mock_storage::get_entry_state(&env, MockStorageType::Temporary, &TestKey::Quote),
+                Some(MockEntryState::Live(50))
+            );
 
-        // Remove the data.
-        storage::remove::<TestKey, TestData>(&env, &key);
+            // Expired temporary entries are reported, then evicted on access.
+            env.ledger().with_mut(|li| li.sequence_number += 51);
+            assert_eq!(
+                mock_storage::get_entry_state(&env, MockStorageType::Temporary, &TestKey::Quote),
+                Some(MockEntryState::Expired)
+            );
+            assert_eq!(
+                mock_storage::get_expired_keys(&env, MockStorageType::Temporary),
+                soroban_sdk::vec![&env, TestKey::Quote.into_val(&env)]
+            );
+            assert!(!storage::has::<_, QuoteData>(&env, &TestKey::Quote));
+            assert_eq!(
+                mock_storage::get_entry_state(&env, MockStorageType::Temporary, &TestKey::Quote),
+                None
+            );
+
+            // Archived persistent entries are reported and cannot be accessed.
+            env.ledger().with_mut(|li| li.sequence_number += 100);
+            assert_eq!(
+                mock_storage::get_entry_state(&env, MockStorageType::Persistent, &TestKey::Balance),
+                Some(MockEntryState::Archived)
+            );
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                storage::get::<_, BalanceData>(&env, &TestKey::Balance);
+            }));
+            assert!(result.is_err(), "Accessing an archived entry should panic.");
+        });
+    }
+
+    #[cfg(feature = "mock-storage")]
+    #[test]
+    fn test_tools_mock_storage_collections_ttl() {
+        use soroban_sdk::symbol_short;
+        use soroban_tools::mock_storage::{self, MockEntryState, MockStorageType};
+
+        #[contracttype]
+        #[derive(Clone, Debug, Eq, PartialEq)]
+        pub struct EntryData {
+            pub value: u32,
+        }
+
+        #[contracttype]
+        #[derive(Clone, Debug, Eq, PartialEq)]
+        pub enum TestKey {
+            Entries,
+        }
+
+        impl_storage!(Temporary, EntryData; ttl(threshold = 50, extend_to = 100));
+
+        let env = Env::default();
+        let contract_id = env.register_contract(None, TestContract);
+        env.as_contract(&contract_id, || {
+            env.ledger().with_mut(|li| li.min_temp_entry_ttl = 16);
+            let state = |key: soroban_sdk::Val| {
+                mock_storage::get_entry_state(&env, MockStorageType::Temporary, &key)
+            };
+            let len_key = (TestKey::Entries, symbol_short!("len"), 0u32).into_val(&env);
+
+            // Elements and metadata are extended on write.
+            let list = storage::StorageVec::<TestKey, EntryData>::new(&TestKey::Entries);
+            list.push(&env, &EntryData { value: 1 });
+            list.push(&env, &EntryData { value: 2 });
+            assert_eq!(state(len_key), Some(MockEntryState::Live(100)));
+            assert_eq!(
+                state((TestKey::Entries, 1u32).into_val(&env)),
+                Some(MockEntryState::Live(100))
+            );
+
+            // Reading an element only extends that element (and the metadata).
+            env.ledger().with_mut(|li| li.sequence_number += 60);
+            assert_eq!(list.get(&env, 0), Some(EntryData { value: 1 }));
+            assert_eq!(
+                state((TestKey::Entries, 0u32).into_val(&env)),
+                Some(MockEntryState::Live(100))
+            );
+            assert_eq!(
+                state((TestKey::Entries, 1u32).into_val(&env)),
+                Some(MockEntryState::Live(40))
+            );
+            assert_eq!(state(len_key), Some(MockEntryState::Live(100)));
+
+            // Map values are extended along with their key slot.
+            mock_storage::reset(&env);
+            let map = storage::StorageMap::<TestKey, u32, EntryData>::new(&TestKey::Entries);
+            map.set(&env, &7, &EntryData { value: 1 });
+            map.set(&env, &8, &EntryData { value: 2 });
+            env.ledger().with_mut(|li| li.sequence_number += 60);
+            assert_eq!(map.get(&env, &8), Some(EntryData { value: 2 }));
+            assert_eq!(
+                state((TestKey::Entries, 8u32).into_val(&env)),
+                Some(MockEntryState::Live(100))
+            );
+            assert_eq!(
+                state((TestKey::Entries, symbol_short!("key"), 1u32).into_val(&env)),
+                Some(MockEntryState::Live(100))
+            );
+            assert_eq!(
+                state((TestKey::Entries, symbol_short!("slot"), 8u32).into_val(&env)),
+                Some(MockEntryState::Live(100))
+            );
+            assert_eq!(
+                state((TestKey::Entries, 7u32).into_val(&env)),
+                Some(MockEntryState::Live(40))
+            );
+        });
     }
 }